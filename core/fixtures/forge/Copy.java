@@ -0,0 +1,15 @@
+import java.nio.file.Files;
+import java.nio.file.Path;
+import java.nio.file.Paths;
+import java.nio.file.StandardCopyOption;
+
+// 测试用的处理器：把第一个参数指向的文件复制到第二个参数
+public class Copy {
+    public static void main(String[] args) throws Exception {
+        Path target = Paths.get(args[1]);
+        if (target.getParent() != null) {
+            Files.createDirectories(target.getParent());
+        }
+        Files.copy(Paths.get(args[0]), target, StandardCopyOption.REPLACE_EXISTING);
+    }
+}
@@ -0,0 +1,12 @@
+#!/bin/sh
+# 从Mojang下载规则测试使用的官方版本Json，替换掉目录里的摘录
+# 需要curl和jq，rule.rs里的测试只检查带规则的条目，替换后不用改测试表
+set -e
+cd "$(dirname "$0")"
+MANIFEST=https://piston-meta.mojang.com/mc/game/version_manifest_v2.json
+manifest=$(curl -fsSL "$MANIFEST")
+for id in 1.8.9 1.12.2 1.16.5 1.19.4 1.20.1; do
+    url=$(printf '%s' "$manifest" | jq -r --arg id "$id" '.versions[] | select(.id == $id) | .url')
+    curl -fsSL "$url" -o "$id.json"
+    echo "$id.json"
+done
@@ -0,0 +1,322 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use crate::auth::MicrosoftAccount;
+use crate::vault::{Vault, VaultKey};
+use crate::yggdrasil::YggdrasilAccount;
+use crate::{CoreError, Result};
+
+// 账户
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Account {
+    Offline {
+        name: String,
+        uuid: String,
+    },
+    Microsoft(MicrosoftAccount),
+    // authlib-injector外置登录
+    Yggdrasil(YggdrasilAccount),
+}
+
+impl Account {
+    pub fn offline(name: &str) -> Account {
+        Account::Offline {
+            name: String::from(name),
+            uuid: offline_uuid(name),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Account::Offline { name, .. } => name,
+            Account::Microsoft(account) => &account.name,
+            Account::Yggdrasil(account) => &account.name,
+        }
+    }
+
+    // 没有横线的UUID
+    pub fn uuid(&self) -> &str {
+        match self {
+            Account::Offline { uuid, .. } => uuid,
+            Account::Microsoft(account) => &account.uuid,
+            Account::Yggdrasil(account) => &account.uuid,
+        }
+    }
+
+    // 离线账户没有令牌，随便给一个
+    pub fn access_token(&self) -> &str {
+        match self {
+            Account::Offline { .. } => "0",
+            Account::Microsoft(account) => &account.access_token,
+            Account::Yggdrasil(account) => &account.access_token,
+        }
+    }
+
+    // ${auth_xuid}，只有微软账户有
+    pub fn xuid(&self) -> &str {
+        match self {
+            Account::Microsoft(account) => &account.xuid,
+            _ => "",
+        }
+    }
+
+    // ${clientid}，只有微软账户有
+    pub fn client_id(&self) -> &str {
+        match self {
+            Account::Microsoft(account) => &account.client_id,
+            _ => "",
+        }
+    }
+
+    // ${user_type}
+    pub fn user_type(&self) -> &str {
+        match self {
+            Account::Offline { .. } => "legacy",
+            Account::Microsoft(_) => "msa",
+            Account::Yggdrasil(_) => "mojang",
+        }
+    }
+
+    // 需要加密保存的令牌
+    fn secrets_mut(&mut self) -> Vec<(&'static str, &mut String)> {
+        match self {
+            Account::Offline { .. } => Vec::new(),
+            Account::Microsoft(account) => vec![
+                ("access_token", &mut account.access_token),
+                ("refresh_token", &mut account.refresh_token),
+            ],
+            Account::Yggdrasil(account) => vec![
+                ("access_token", &mut account.access_token),
+                ("client_token", &mut account.client_token),
+            ],
+        }
+    }
+
+    // 显示在界面上的账户类型
+    pub fn type_name(&self) -> &str {
+        match self {
+            Account::Offline { .. } => "Offline",
+            Account::Microsoft(_) => "Microsoft",
+            Account::Yggdrasil(_) => "Authlib-Injector",
+        }
+    }
+}
+
+// 与原版相同的离线UUID：对"OfflinePlayer:<名字>"做MD5，再设置为第3版UUID
+pub fn offline_uuid(name: &str) -> String {
+    let mut hash: [u8; 16] = Md5::digest(format!("OfflinePlayer:{}", name).as_bytes()).into();
+    hash[6] = (hash[6] & 0x0f) | 0x30;
+    hash[8] = (hash[8] & 0x3f) | 0x80;
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct AccountFile {
+    selected: Option<String>,
+    accounts: Vec<Account>,
+}
+
+// 保存在文件里的账户列表，有凭据库时令牌加密保存在凭据库里
+pub struct AccountStore {
+    path: PathBuf,
+    file: AccountFile,
+    vault: Option<Vault>,
+}
+
+impl AccountStore {
+    // 文件不存在时返回空的列表
+    pub fn load(path: impl Into<PathBuf>) -> Result<AccountStore> {
+        let path = path.into();
+        let file = if path.exists() {
+            serde_json::from_str(&read_to_string(&path)?)?
+        } else {
+            AccountFile::default()
+        };
+        Ok(AccountStore { path, file, vault: None })
+    }
+
+    // 界面使用的账户列表，令牌保存在同一目录下用本机密钥加密的凭据库里
+    pub fn open(path: impl Into<PathBuf>) -> Result<AccountStore> {
+        let path = path.into();
+        let vault = Vault::open(path.with_extension("vault"), &VaultKey::Machine)?;
+        AccountStore::load(path)?.with_vault(vault)
+    }
+
+    // 从凭据库里取回令牌
+    pub fn with_vault(mut self, vault: Vault) -> Result<AccountStore> {
+        for account in self.file.accounts.iter_mut() {
+            let uuid = String::from(account.uuid());
+            for (name, secret) in account.secrets_mut() {
+                if let Some(value) = vault.get(&format!("{}.{}", uuid, name))? {
+                    *secret = value;
+                }
+            }
+        }
+        self.vault = Some(vault);
+        Ok(self)
+    }
+
+    // 没有凭据库时不会把令牌明文写进文件
+    pub fn save(&mut self) -> Result<()> {
+        if self.vault.is_none() && self.file.accounts.iter_mut().any(|a| a.secrets_mut().iter().any(|(_, s)| !s.is_empty())) {
+            return Err(CoreError::Vault(String::from("No vault to keep the account tokens in")));
+        }
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+        let content = match self.vault.as_mut() {
+            Some(vault) => {
+                // 令牌放进凭据库，文件里只留空字符串
+                let mut accounts = self.file.accounts.clone();
+                for account in accounts.iter_mut() {
+                    let uuid = String::from(account.uuid());
+                    for (name, secret) in account.secrets_mut() {
+                        vault.put(&format!("{}.{}", uuid, name), secret)?;
+                        secret.clear();
+                    }
+                }
+                let file = AccountFile { selected: self.file.selected.clone(), accounts };
+                serde_json::to_string_pretty(&file)?
+            }
+            None => serde_json::to_string_pretty(&self.file)?,
+        };
+        write(&self.path, content)?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn accounts(&self) -> &[Account] {
+        &self.file.accounts
+    }
+
+    // 同一个UUID的账户会被替换，第一个账户自动成为默认账户
+    pub fn add(&mut self, account: Account) {
+        match self.file.accounts.iter_mut().find(|a| a.uuid() == account.uuid()) {
+            Some(existing) => *existing = account,
+            None => {
+                if self.file.selected.is_none() {
+                    self.file.selected = Some(String::from(account.uuid()));
+                }
+                self.file.accounts.push(account);
+            }
+        }
+    }
+
+    // 登出，立即重写账户文件和凭据库。不会擦除磁盘上的旧数据，
+    // 但有凭据库时令牌只以密文的形式写入过磁盘
+    pub fn remove(&mut self, uuid: &str) -> Result<Option<Account>> {
+        let Some(index) = self.file.accounts.iter().position(|a| a.uuid() == uuid) else {
+            return Ok(None)
+        };
+        let account = self.file.accounts.remove(index);
+        if self.file.selected.as_deref() == Some(uuid) {
+            self.file.selected = self.file.accounts.first().map(|a| String::from(a.uuid()));
+        }
+        if let Some(vault) = self.vault.as_mut() {
+            vault.remove_prefix(&format!("{}.", uuid))?;
+        }
+        self.save()?;
+        Ok(Some(account))
+    }
+
+    pub fn select(&mut self, uuid: &str) -> Result<()> {
+        if !self.file.accounts.iter().any(|a| a.uuid() == uuid) {
+            return Err(CoreError::Auth(format!("No account with UUID {}!", uuid)));
+        }
+        self.file.selected = Some(String::from(uuid));
+        Ok(())
+    }
+
+    pub fn selected(&self) -> Option<&Account> {
+        let uuid = self.file.selected.as_deref()?;
+        self.file.accounts.iter().find(|a| a.uuid() == uuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_uuid_matches_vanilla() {
+        // UUID.nameUUIDFromBytes("OfflinePlayer:Notch")
+        assert_eq!(offline_uuid("Notch"), "b50ad385829d3141a2167e7d7539ba7f");
+        assert_eq!(Account::offline("Notch").user_type(), "legacy");
+    }
+
+    #[test]
+    fn store_round_trip() {
+        let path = std::env::temp_dir().join(format!("akiraka-accounts-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut store = AccountStore::load(&path).unwrap();
+        store.add(Account::offline("Alex"));
+        store.add(Account::offline("Steve"));
+        store.add(Account::Microsoft(MicrosoftAccount {
+            name: String::from("Notch"),
+            uuid: String::from("069a79f444e94726a5befca90e38aaf5"),
+            access_token: String::from("token"),
+            refresh_token: String::from("refresh"),
+            expires_at: 0,
+            xuid: String::from("hash"),
+            client_id: String::from("client"),
+        }));
+        assert_eq!(store.selected().unwrap().name(), "Alex");
+        store.select("069a79f444e94726a5befca90e38aaf5").unwrap();
+        assert!(store.select("missing").is_err());
+        // 没有凭据库时不能保存令牌
+        assert!(matches!(store.save(), Err(CoreError::Vault(_))));
+        assert!(!path.exists());
+        let vault_path = path.with_extension("vault");
+        let key = VaultKey::Passphrase(String::from("key"));
+        let vault = Vault::open_with_iterations(&vault_path, &key, 10).unwrap();
+        let mut store = store.with_vault(vault).unwrap();
+        store.save().unwrap();
+
+        let vault = Vault::open(&vault_path, &key).unwrap();
+        let mut store = AccountStore::load(&path).unwrap().with_vault(vault).unwrap();
+        assert_eq!(store.accounts().len(), 3);
+        assert_eq!(store.selected().unwrap().type_name(), "Microsoft");
+        store.remove("069a79f444e94726a5befca90e38aaf5").unwrap().unwrap();
+        assert_eq!(store.selected().unwrap().name(), "Alex");
+        // 登出后文件里已经没有这个账户
+        assert!(!read_to_string(&path).unwrap().contains("Notch"));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&vault_path);
+    }
+
+    #[test]
+    fn tokens_are_kept_in_vault() {
+        let path = std::env::temp_dir().join(format!("akiraka-accounts-vault-{}.json", std::process::id()));
+        let vault_path = path.with_extension("vault");
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&vault_path);
+        let key = VaultKey::Passphrase(String::from("key"));
+        let vault = Vault::open_with_iterations(&vault_path, &key, 10).unwrap();
+        let mut store = AccountStore::load(&path).unwrap().with_vault(vault).unwrap();
+        store.add(Account::Microsoft(MicrosoftAccount {
+            name: String::from("Notch"),
+            uuid: String::from("069a79f444e94726a5befca90e38aaf5"),
+            access_token: String::from("plain-access"),
+            refresh_token: String::from("plain-refresh"),
+            expires_at: 0,
+            xuid: String::from("hash"),
+            client_id: String::from("client"),
+        }));
+        store.save().unwrap();
+        let content = read_to_string(&path).unwrap();
+        assert!(!content.contains("plain-refresh") && !content.contains("plain-access"));
+
+        let vault = Vault::open(&vault_path, &key).unwrap();
+        let mut store = AccountStore::load(&path).unwrap().with_vault(vault).unwrap();
+        assert_eq!(store.selected().unwrap().access_token(), "plain-access");
+        store.remove("069a79f444e94726a5befca90e38aaf5").unwrap();
+        assert!(Vault::open(&vault_path, &key).unwrap().names().is_empty());
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&vault_path);
+    }
+}
@@ -0,0 +1,389 @@
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::error::missing;
+use crate::progress::CancelToken;
+use crate::{CoreError, Result};
+
+// 微软登录需要的所有地址，测试时可以换成本地服务器
+#[derive(Clone, Debug)]
+pub struct AuthEndpoints {
+    pub device_code: String,
+    pub token: String,
+    pub xbox_user: String,
+    pub xsts: String,
+    pub minecraft_login: String,
+    pub minecraft_profile: String,
+}
+
+impl Default for AuthEndpoints {
+    fn default() -> Self {
+        AuthEndpoints {
+            device_code: String::from("https://login.microsoftonline.com/consumers/oauth2/v2.0/devicecode"),
+            token: String::from("https://login.microsoftonline.com/consumers/oauth2/v2.0/token"),
+            xbox_user: String::from("https://user.auth.xboxlive.com/user/authenticate"),
+            xsts: String::from("https://xsts.auth.xboxlive.com/xsts/authorize"),
+            minecraft_login: String::from("https://api.minecraftservices.com/authentication/login_with_xbox"),
+            minecraft_profile: String::from("https://api.minecraftservices.com/minecraft/profile"),
+        }
+    }
+}
+
+impl AuthEndpoints {
+    // 所有地址都指向同一个服务器，路径与官方相同
+    pub fn with_base(base: &str) -> AuthEndpoints {
+        AuthEndpoints {
+            device_code: format!("{}/consumers/oauth2/v2.0/devicecode", base),
+            token: format!("{}/consumers/oauth2/v2.0/token", base),
+            xbox_user: format!("{}/user/authenticate", base),
+            xsts: format!("{}/xsts/authorize", base),
+            minecraft_login: format!("{}/authentication/login_with_xbox", base),
+            minecraft_profile: format!("{}/minecraft/profile", base),
+        }
+    }
+}
+
+// 设备代码，需要把user_code和verification_uri展示给用户
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceCode {
+    pub user_code: String,
+    pub device_code: String,
+    pub verification_uri: String,
+    pub message: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+// 微软的OAuth令牌
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MicrosoftToken {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+// 登录完成的微软账户
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MicrosoftAccount {
+    pub name: String,
+    pub uuid: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    // Unix时间（秒）
+    pub expires_at: u64,
+    // XSTS返回的用户哈希，作为${auth_xuid}
+    #[serde(default)]
+    pub xuid: String,
+    // 登录时使用的Azure应用ID，作为${clientid}
+    #[serde(default)]
+    pub client_id: String,
+}
+
+impl MicrosoftAccount {
+    pub fn is_expired(&self) -> bool {
+        now() >= self.expires_at
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn str_field(json: &Value, key: &str) -> Result<String> {
+    json[key].as_str().map(String::from).ok_or_else(|| missing(key))
+}
+
+// 微软正版登录：设备代码 -> Xbox Live -> XSTS -> Minecraft
+pub struct MicrosoftAuth {
+    client: Client,
+    client_id: String,
+    endpoints: AuthEndpoints,
+}
+
+impl MicrosoftAuth {
+    pub fn new(client_id: impl Into<String>) -> MicrosoftAuth {
+        MicrosoftAuth {
+            client: Client::new(),
+            client_id: client_id.into(),
+            endpoints: AuthEndpoints::default(),
+        }
+    }
+
+    pub fn with_endpoints(mut self, endpoints: AuthEndpoints) -> MicrosoftAuth {
+        self.endpoints = endpoints;
+        self
+    }
+
+    pub fn with_client(mut self, client: Client) -> MicrosoftAuth {
+        self.client = client;
+        self
+    }
+
+    // 发送请求，非2xx时把响应内容放进错误里
+    fn send(&self, request: RequestBuilder) -> Result<Value> {
+        let response = request.header("Accept", "application/json").send()?;
+        let status = response.status();
+        let text = response.text()?;
+        if !status.is_success() {
+            return Err(CoreError::Auth(format!("{} {}", status.as_u16(), text)));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    // 第一步：申请设备代码
+    pub fn request_device_code(&self) -> Result<DeviceCode> {
+        let json = self.send(self.client.post(&self.endpoints.device_code).form(&[
+            ("client_id", self.client_id.as_str()),
+            ("scope", "XboxLive.signin offline_access"),
+        ]))?;
+        Ok(DeviceCode {
+            user_code: str_field(&json, "user_code")?,
+            device_code: str_field(&json, "device_code")?,
+            verification_uri: str_field(&json, "verification_uri")?,
+            message: json["message"].as_str().map(String::from).unwrap_or_default(),
+            expires_in: json["expires_in"].as_u64().unwrap_or(900),
+            interval: json["interval"].as_u64().unwrap_or(5),
+        })
+    }
+
+    // 第二步：等待用户在浏览器里完成登录
+    pub fn poll_token(&self, code: &DeviceCode, cancel: &CancelToken) -> Result<MicrosoftToken> {
+        let deadline = now() + code.expires_in;
+        let mut interval = code.interval;
+        loop {
+            cancel.check()?;
+            let response = self.client.post(&self.endpoints.token)
+                .header("Accept", "application/json")
+                .form(&[
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                    ("client_id", self.client_id.as_str()),
+                    ("device_code", code.device_code.as_str()),
+                ])
+                .send()?;
+            let json: Value = serde_json::from_str(&response.text()?)?;
+            match json["error"].as_str() {
+                None => return token_from_json(&json),
+                Some("authorization_pending") => {}
+                // 服务器要求放慢速度
+                Some("slow_down") => interval += 5,
+                Some(error) => return Err(CoreError::Auth(String::from(error))),
+            }
+            if now() >= deadline {
+                return Err(CoreError::Auth(String::from("expired_token")));
+            }
+            for _ in 0..interval * 10 {
+                cancel.check()?;
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+
+    // 用刷新令牌换新的微软令牌
+    pub fn refresh_token(&self, refresh_token: &str) -> Result<MicrosoftToken> {
+        let json = self.send(self.client.post(&self.endpoints.token).form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", self.client_id.as_str()),
+            ("refresh_token", refresh_token),
+            ("scope", "XboxLive.signin offline_access"),
+        ]))?;
+        token_from_json(&json)
+    }
+
+    // Xbox Live认证，返回令牌和用户哈希
+    fn xbox_live(&self, access_token: &str) -> Result<(String, String)> {
+        let json = self.send(self.client.post(&self.endpoints.xbox_user).json(&json!({
+            "Properties": {
+                "AuthMethod": "RPS",
+                "SiteName": "user.auth.xboxlive.com",
+                "RpsTicket": format!("d={}", access_token)
+            },
+            "RelyingParty": "http://auth.xboxlive.com",
+            "TokenType": "JWT"
+        })))?;
+        let uhs = json["DisplayClaims"]["xui"][0]["uhs"].as_str().ok_or_else(|| missing("DisplayClaims.xui.uhs"))?;
+        Ok((str_field(&json, "Token")?, String::from(uhs)))
+    }
+
+    // XSTS认证，返回令牌和用户哈希
+    fn xsts(&self, xbox_token: &str) -> Result<(String, String)> {
+        let response = self.client.post(&self.endpoints.xsts)
+            .header("Accept", "application/json")
+            .json(&json!({
+                "Properties": {
+                    "SandboxId": "RETAIL",
+                    "UserTokens": [xbox_token]
+                },
+                "RelyingParty": "rp://api.minecraftservices.com/",
+                "TokenType": "JWT"
+            }))
+            .send()?;
+        let status = response.status();
+        let json: Value = serde_json::from_str(&response.text()?).unwrap_or(Value::Null);
+        if status.as_u16() == 401 {
+            let reason = match json["XErr"].as_u64() {
+                Some(2148916233) => "This Microsoft account has no Xbox account",
+                Some(2148916235) => "Xbox Live is not available in this country",
+                Some(2148916236) | Some(2148916237) => "This account needs adult verification",
+                Some(2148916238) => "This account is a child account and must be added to a family",
+                _ => "XSTS authorization was denied",
+            };
+            return Err(CoreError::Auth(String::from(reason)));
+        }
+        if !status.is_success() {
+            return Err(CoreError::Auth(format!("{} {}", status.as_u16(), json)));
+        }
+        let uhs = json["DisplayClaims"]["xui"][0]["uhs"].as_str().ok_or_else(|| missing("DisplayClaims.xui.uhs"))?;
+        Ok((str_field(&json, "Token")?, String::from(uhs)))
+    }
+
+    // 第三步：用微软令牌完成剩下的登录
+    pub fn login(&self, token: MicrosoftToken) -> Result<MicrosoftAccount> {
+        let (xbox_token, _) = self.xbox_live(&token.access_token)?;
+        let (xsts_token, uhs) = self.xsts(&xbox_token)?;
+        let json = self.send(self.client.post(&self.endpoints.minecraft_login).json(&json!({
+            "identityToken": format!("XBL3.0 x={};{}", uhs, xsts_token)
+        })))?;
+        let access_token = str_field(&json, "access_token")?;
+        let expires_at = now() + json["expires_in"].as_u64().unwrap_or(86400);
+
+        let profile = self.send(self.client.get(&self.endpoints.minecraft_profile).bearer_auth(&access_token))
+            .map_err(|e| match e {
+                // 没有购买游戏
+                CoreError::Auth(message) if message.starts_with("404") => {
+                    CoreError::Auth(String::from("This account does not own Minecraft"))
+                }
+                e => e,
+            })?;
+        Ok(MicrosoftAccount {
+            name: str_field(&profile, "name")?,
+            uuid: str_field(&profile, "id")?,
+            access_token,
+            refresh_token: token.refresh_token,
+            expires_at,
+            xuid: uhs,
+            client_id: self.client_id.clone(),
+        })
+    }
+
+    // 刷新账户，微软令牌也会一起刷新
+    pub fn refresh(&self, account: &MicrosoftAccount) -> Result<MicrosoftAccount> {
+        let token = self.refresh_token(&account.refresh_token)?;
+        self.login(token)
+    }
+}
+
+fn token_from_json(json: &Value) -> Result<MicrosoftToken> {
+    Ok(MicrosoftToken {
+        access_token: str_field(json, "access_token")?,
+        refresh_token: str_field(json, "refresh_token")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use crate::test_server::{Response, TestServer};
+
+    // 模拟微软、Xbox和Minecraft的服务器
+    fn mock_server(pending: usize, owns_game: bool) -> TestServer {
+        let polls = Arc::new(AtomicUsize::new(0));
+        TestServer::start(move |req| match req.path.as_str() {
+            "/consumers/oauth2/v2.0/devicecode" => {
+                assert!(req.body_str().contains("client_id=test-client"));
+                Response::json(200, &json!({
+                    "user_code": "ABCD1234",
+                    "device_code": "device",
+                    "verification_uri": "https://microsoft.com/devicelogin",
+                    "expires_in": 60,
+                    "interval": 0,
+                    "message": "Go to the page"
+                }))
+            }
+            "/consumers/oauth2/v2.0/token" => {
+                let body = req.body_str();
+                if body.contains("grant_type=refresh_token") {
+                    assert!(body.contains("refresh_token=refresh-1"));
+                    return Response::json(200, &json!({"access_token": "ms-2", "refresh_token": "refresh-2"}));
+                }
+                if polls.fetch_add(1, Ordering::SeqCst) < pending {
+                    return Response::json(400, &json!({"error": "authorization_pending"}));
+                }
+                Response::json(200, &json!({"access_token": "ms-1", "refresh_token": "refresh-1"}))
+            }
+            "/user/authenticate" => {
+                let body: Value = serde_json::from_slice(&req.body).unwrap();
+                let ticket = body["Properties"]["RpsTicket"].as_str().unwrap().to_string();
+                Response::json(200, &json!({
+                    "Token": format!("xbl-{}", ticket),
+                    "DisplayClaims": {"xui": [{"uhs": "hash"}]}
+                }))
+            }
+            "/xsts/authorize" => {
+                let body: Value = serde_json::from_slice(&req.body).unwrap();
+                let token = body["Properties"]["UserTokens"][0].as_str().unwrap().to_string();
+                Response::json(200, &json!({
+                    "Token": format!("xsts-{}", token),
+                    "DisplayClaims": {"xui": [{"uhs": "hash"}]}
+                }))
+            }
+            "/authentication/login_with_xbox" => {
+                let body: Value = serde_json::from_slice(&req.body).unwrap();
+                let identity = body["identityToken"].as_str().unwrap().to_string();
+                Response::json(200, &json!({"access_token": format!("mc({})", identity), "expires_in": 3600}))
+            }
+            "/minecraft/profile" if owns_game => Response::json(200, &json!({
+                "id": "069a79f444e94726a5befca90e38aaf5",
+                "name": "Notch"
+            })),
+            _ => Response::not_found(),
+        })
+    }
+
+    fn auth(server: &TestServer) -> MicrosoftAuth {
+        MicrosoftAuth::new("test-client").with_endpoints(AuthEndpoints::with_base(&server.base_url))
+    }
+
+    #[test]
+    fn device_code_flow() {
+        let server = mock_server(2, true);
+        let auth = auth(&server);
+        let code = auth.request_device_code().unwrap();
+        assert_eq!(code.user_code, "ABCD1234");
+
+        let token = auth.poll_token(&code, &CancelToken::new()).unwrap();
+        assert_eq!(token.access_token, "ms-1");
+
+        let account = auth.login(token).unwrap();
+        assert_eq!(account.name, "Notch");
+        assert_eq!(account.uuid, "069a79f444e94726a5befca90e38aaf5");
+        assert_eq!(account.access_token, "mc(XBL3.0 x=hash;xsts-xbl-d=ms-1)");
+        assert!(!account.is_expired());
+        assert_eq!((account.xuid.as_str(), account.client_id.as_str()), ("hash", "test-client"));
+        assert_eq!(server.requests().iter().filter(|r| r.ends_with("/token")).count(), 3);
+
+        let refreshed = auth.refresh(&account).unwrap();
+        assert_eq!(refreshed.refresh_token, "refresh-2");
+        assert_eq!(refreshed.access_token, "mc(XBL3.0 x=hash;xsts-xbl-d=ms-2)");
+    }
+
+    #[test]
+    fn account_without_game() {
+        let server = mock_server(0, false);
+        let auth = auth(&server);
+        let token = MicrosoftToken { access_token: String::from("ms"), refresh_token: String::from("r") };
+        assert!(matches!(auth.login(token), Err(CoreError::Auth(_))));
+    }
+
+    #[test]
+    fn cancel_while_polling() {
+        let server = mock_server(usize::MAX, true);
+        let auth = auth(&server);
+        let code = auth.request_device_code().unwrap();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert!(matches!(auth.poll_token(&code, &cancel), Err(CoreError::Cancelled)));
+    }
+}
@@ -0,0 +1,225 @@
+use std::env;
+use std::env::consts::OS;
+use std::fs::{create_dir_all, read_to_string, rename, write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use crate::jvm::{GcPreset, JvmSettings, MemorySetting};
+use crate::source::{DownloadSources, Mirror};
+use crate::{CoreError, Result};
+
+// 当前的配置文件版本，修改格式时加一并在MIGRATIONS里加上迁移函数
+pub const CONFIG_VERSION: u32 = 2;
+pub const CONFIG_FILE: &str = "config.json";
+
+// 下载源
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    #[default]
+    Official,
+    Bmclapi,
+    // 使用custom_mirror里的地址
+    Custom,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    // 跟随系统
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+// 启动器设置，缺少的项使用默认值
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub version: u32,
+    // 游戏根目录，versions、libraries和assets所在的目录
+    pub game_root: PathBuf,
+    // 用户添加或找到的爪哇
+    pub java: Vec<PathBuf>,
+    // 默认的内存设置，实例没有设置时使用
+    pub memory: MemorySetting,
+    pub gc: GcPreset,
+    // 所有实例共用的JVM参数
+    pub jvm_args: Vec<String>,
+    pub download_source: SourceKind,
+    pub custom_mirror: Option<String>,
+    // 同时下载的线程数
+    pub threads: usize,
+    pub theme: Theme,
+    pub language: String,
+    // 上次打开的页面
+    pub page: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            game_root: PathBuf::from(".minecraft"),
+            java: Vec::new(),
+            memory: MemorySetting::Auto,
+            gc: GcPreset::Default,
+            jvm_args: Vec::new(),
+            download_source: SourceKind::Official,
+            custom_mirror: None,
+            threads: 8,
+            theme: Theme::System,
+            language: String::from("en-US"),
+            page: String::new(),
+        }
+    }
+}
+
+// 版本n到n+1的迁移函数
+type Migration = fn(&mut Map<String, Value>);
+
+// 1：memory是MB数，null为自动
+fn migrate_v1(config: &mut Map<String, Value>) {
+    let memory = match config.get("memory") {
+        Some(Value::Number(max)) => json!({"mode": "fixed", "max": max}),
+        Some(Value::Null) => json!({"mode": "auto"}),
+        _ => return,
+    };
+    config.insert(String::from("memory"), memory);
+}
+
+// 第一个版本是1，MIGRATIONS[n - 1]把版本n升级到n+1
+const MIGRATIONS: [Migration; CONFIG_VERSION as usize - 1] = [migrate_v1];
+
+// 把旧版本的配置逐个版本升级到当前版本
+fn migrate(mut value: Value) -> Result<Value> {
+    let Some(config) = value.as_object_mut() else {
+        return Err(CoreError::Manifest(String::from("Config is not an object!")));
+    };
+    let version = config.get("version").and_then(Value::as_u64).unwrap_or(1) as u32;
+    if version == 0 || version > CONFIG_VERSION {
+        return Err(CoreError::Manifest(format!("Unsupported config version {}, the newest supported version is {}!", version, CONFIG_VERSION)));
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(config);
+    }
+    config.insert(String::from("version"), Value::from(CONFIG_VERSION));
+    Ok(value)
+}
+
+// 系统的配置目录
+pub fn config_dir() -> Option<PathBuf> {
+    let home = || env::var_os(if OS == "windows" { "USERPROFILE" } else { "HOME" }).map(PathBuf::from);
+    match OS {
+        "windows" => env::var_os("APPDATA").map(PathBuf::from).map(|d| d.join("Akiraka")),
+        "macos" => home().map(|h| h.join("Library/Application Support/Akiraka")),
+        _ => env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+            .or_else(|| home().map(|h| h.join(".config")))
+            .map(|d| d.join("akiraka")),
+    }
+}
+
+impl Config {
+    // 配置文件的默认位置，找不到配置目录时放在当前目录
+    pub fn default_path() -> PathBuf {
+        config_dir().unwrap_or_default().join(CONFIG_FILE)
+    }
+
+    // 文件不存在时返回默认配置
+    pub fn load(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let value: Value = serde_json::from_str(&read_to_string(path)?)?;
+        Ok(serde_json::from_value(migrate(value)?)?)
+    }
+
+    // 先写临时文件再替换，避免写到一半时配置损坏
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let temp = path.with_extension("json.tmp");
+        write(&temp, serde_json::to_string_pretty(self)?)?;
+        rename(&temp, path)?;
+        Ok(())
+    }
+
+    // 游戏根目录下安装官方爪哇运行时的目录
+    pub fn runtime_dir(&self) -> PathBuf {
+        self.game_root.join("runtime")
+    }
+
+    // 账户列表文件
+    pub fn accounts_file(&self) -> PathBuf {
+        self.game_root.join("accounts.json")
+    }
+
+    // 全局的JVM设置
+    pub fn jvm_settings(&self) -> JvmSettings {
+        JvmSettings {
+            memory: self.memory,
+            gc: self.gc,
+            args: self.jvm_args.clone(),
+        }
+    }
+
+    pub fn download_sources(&self) -> DownloadSources {
+        match (self.download_source, self.custom_mirror.as_deref()) {
+            (SourceKind::Bmclapi, _) => DownloadSources::with_fallback(Mirror::bmclapi()),
+            (SourceKind::Custom, Some(base)) if !base.is_empty() => DownloadSources::with_fallback(Mirror::custom(base)),
+            _ => DownloadSources::official(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("akiraka-config-{}-{}", name, std::process::id())).join(CONFIG_FILE)
+    }
+
+    #[test]
+    fn round_trips_and_defaults() {
+        let path = temp_path("round-trip");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+        let config = Config {
+            java: vec![PathBuf::from("/usr/bin/java")],
+            memory: MemorySetting::Fixed { max: 4096, min: None },
+            download_source: SourceKind::Bmclapi,
+            theme: Theme::Dark,
+            ..Config::default()
+        };
+        config.save(&path).unwrap();
+        assert_eq!(Config::load(&path).unwrap(), config);
+        assert_eq!(config.download_sources().names(), vec!["BMCLAPI", "Official"]);
+        assert_eq!(config.runtime_dir(), Path::new(".minecraft/runtime"));
+        let custom = Config { download_source: SourceKind::Custom, custom_mirror: Some(String::from("https://mirror.example.com")), ..Config::default() };
+        assert_eq!(custom.download_sources().names(), vec!["Custom", "Official"]);
+
+        // 缺少的字段使用默认值
+        write(&path, r#"{"version": 2, "threads": 16}"#).unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!((config.threads, config.language.as_str()), (16, "en-US"));
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn migrates_old_versions() {
+        let v1: Config = serde_json::from_value(migrate(json!({"version": 1, "memory": 6144, "threads": 4})).unwrap()).unwrap();
+        assert_eq!(v1.version, CONFIG_VERSION);
+        assert_eq!(v1.threads, 4);
+        assert_eq!(v1.memory, MemorySetting::Fixed { max: 6144, min: None });
+        assert_eq!(v1.jvm_settings().arguments(Path::new("missing"), 17).unwrap(), vec!["-Xmx6144M"]);
+
+        let auto: Config = serde_json::from_value(migrate(json!({"version": 1, "memory": null})).unwrap()).unwrap();
+        assert_eq!(auto.memory, MemorySetting::Auto);
+        assert!(migrate(json!({"version": 0})).is_err());
+        assert!(migrate(json!({"version": CONFIG_VERSION + 1})).is_err());
+    }
+}
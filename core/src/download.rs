@@ -0,0 +1,271 @@
+use std::fs::{create_dir_all, remove_file, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use reqwest::blocking::Client;
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use crate::error::missing;
+use crate::model::Download;
+use crate::progress::CancelToken;
+use crate::{CoreError, Result};
+
+// 单个文件的下载任务
+#[derive(Clone, Debug)]
+pub struct DownloadTask {
+    pub url: String,
+    pub path: PathBuf,
+    pub sha1: Option<String>,
+    pub size: Option<u64>,
+}
+
+// 下载的结果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DownloadStatus {
+    // 文件已经是正确的
+    Skipped,
+    // 从头下载
+    Downloaded,
+    // 从断点继续下载
+    Resumed,
+}
+
+impl DownloadTask {
+    pub fn new(url: impl Into<String>, path: impl Into<PathBuf>) -> DownloadTask {
+        DownloadTask {
+            url: url.into(),
+            path: path.into(),
+            sha1: None,
+            size: None,
+        }
+    }
+
+    pub fn with_sha1(mut self, sha1: Option<&str>) -> DownloadTask {
+        self.sha1 = sha1.map(str::to_lowercase);
+        self
+    }
+
+    pub fn with_size(mut self, size: Option<u64>) -> DownloadTask {
+        self.size = size;
+        self
+    }
+
+    // 从版本Json里的下载信息创建（url、sha1、size）
+    pub fn from_json(value: &Value, path: impl Into<PathBuf>) -> Result<DownloadTask> {
+        let url = value["url"].as_str().ok_or_else(|| missing("url"))?;
+        Ok(DownloadTask::new(url, path)
+            .with_sha1(value["sha1"].as_str())
+            .with_size(value["size"].as_u64()))
+    }
+
+    pub fn from_download(download: &Download, path: impl Into<PathBuf>) -> DownloadTask {
+        DownloadTask::new(download.url.as_str(), path)
+            .with_sha1(download.sha1.as_deref())
+            .with_size(download.size)
+    }
+
+    // 是否有可以校验的信息
+    pub fn is_verifiable(&self) -> bool {
+        self.sha1.is_some() || self.size.is_some()
+    }
+}
+
+// 计算文件的SHA-1
+pub fn file_sha1(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha1::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// 检查文件是否完整，没有校验信息时视为不完整
+pub fn verify(task: &DownloadTask) -> Result<bool> {
+    if !task.is_verifiable() || !task.path.is_file() {
+        return Ok(false);
+    }
+    if let Some(size) = task.size {
+        if task.path.metadata()?.len() != size {
+            return Ok(false);
+        }
+    }
+    if let Some(sha1) = &task.sha1 {
+        return Ok(file_sha1(&task.path)? == *sha1);
+    }
+    Ok(true)
+}
+
+// 分块复制，每块之间检查是否取消
+fn copy(response: &mut impl Read, file: &mut File, cancel: &CancelToken) -> Result<()> {
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        cancel.check()?;
+        let len = response.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        file.write_all(&buf[..len])?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+// 下载一次，不做重试
+fn fetch(client: &Client, task: &DownloadTask, cancel: &CancelToken) -> Result<DownloadStatus> {
+    if let Some(parent) = task.path.parent() {
+        create_dir_all(parent)?;
+    }
+
+    // 已经下载的部分
+    let existing = if task.path.is_file() { task.path.metadata()?.len() } else { 0 };
+    let resume = match task.size {
+        Some(size) => existing > 0 && existing < size,
+        None => false,
+    };
+
+    let mut request = client.get(&task.url);
+    if resume {
+        request = request.header(RANGE, format!("bytes={}-", existing));
+    }
+    let mut response = request.send()?.error_for_status()?;
+
+    let status = if resume && response.status() == StatusCode::PARTIAL_CONTENT {
+        let mut file = OpenOptions::new().append(true).open(&task.path)?;
+        copy(&mut response, &mut file, cancel)?;
+        DownloadStatus::Resumed
+    } else {
+        // 服务器不支持Range时从头下载
+        let mut file = File::create(&task.path)?;
+        copy(&mut response, &mut file, cancel)?;
+        DownloadStatus::Downloaded
+    };
+
+    if task.is_verifiable() && !verify(task)? {
+        let actual = file_sha1(&task.path).unwrap_or_default();
+        let _ = remove_file(&task.path);
+        return Err(CoreError::Checksum(format!(
+            "{} (expected sha1 {} size {:?}, got sha1 {})",
+            task.url,
+            task.sha1.as_deref().unwrap_or("-"),
+            task.size,
+            actual
+        )));
+    }
+    Ok(status)
+}
+
+// 下崽函数：跳过已经正确的文件，断点续传，损坏时重新下载
+pub fn download(client: &Client, task: &DownloadTask, retries: usize, cancel: &CancelToken) -> Result<DownloadStatus> {
+    cancel.check()?;
+    if verify(task)? {
+        return Ok(DownloadStatus::Skipped);
+    }
+    // 大小已经超过或等于预期却校验失败，说明文件损坏
+    if task.path.is_file() {
+        let len = task.path.metadata()?.len();
+        if task.size.is_none_or(|size| len >= size) {
+            remove_file(&task.path)?;
+        }
+    }
+
+    let mut last_error = None;
+    for _ in 0..=retries {
+        cancel.check()?;
+        match fetch(client, task, cancel) {
+            Ok(status) => return Ok(status),
+            Err(e) if e.is_retryable() => last_error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| CoreError::Checksum(task.url.clone())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::TestServer;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("akiraka-download-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const DATA: &[u8] = b"The quick brown fox jumps over the lazy dog";
+    const DATA_SHA1: &str = "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12";
+
+    #[test]
+    fn sha1_of_file() {
+        let dir = temp_dir("sha1");
+        std::fs::write(dir.join("a"), DATA).unwrap();
+        assert_eq!(file_sha1(&dir.join("a")).unwrap(), DATA_SHA1);
+    }
+
+    #[test]
+    fn skips_correct_file() {
+        let dir = temp_dir("skip");
+        let server = TestServer::with_files(vec![("/a", DATA.to_vec())]);
+        std::fs::write(dir.join("a"), DATA).unwrap();
+        let task = DownloadTask::new(server.url("/a"), dir.join("a"))
+            .with_sha1(Some(DATA_SHA1))
+            .with_size(Some(DATA.len() as u64));
+        assert_eq!(download(&Client::new(), &task, 0, &CancelToken::new()).unwrap(), DownloadStatus::Skipped);
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn resumes_partial_file() {
+        let dir = temp_dir("resume");
+        let server = TestServer::with_files(vec![("/a", DATA.to_vec())]);
+        std::fs::write(dir.join("a"), &DATA[..10]).unwrap();
+        let task = DownloadTask::new(server.url("/a"), dir.join("a"))
+            .with_sha1(Some(DATA_SHA1))
+            .with_size(Some(DATA.len() as u64));
+        assert_eq!(download(&Client::new(), &task, 0, &CancelToken::new()).unwrap(), DownloadStatus::Resumed);
+        assert_eq!(std::fs::read(dir.join("a")).unwrap(), DATA);
+    }
+
+    #[test]
+    fn replaces_corrupt_file() {
+        let dir = temp_dir("corrupt");
+        let server = TestServer::with_files(vec![("/a", DATA.to_vec())]);
+        let mut corrupt = DATA.to_vec();
+        corrupt[0] = b'X';
+        std::fs::write(dir.join("a"), corrupt).unwrap();
+        let task = DownloadTask::new(server.url("/a"), dir.join("a"))
+            .with_sha1(Some(DATA_SHA1))
+            .with_size(Some(DATA.len() as u64));
+        assert_eq!(download(&Client::new(), &task, 0, &CancelToken::new()).unwrap(), DownloadStatus::Downloaded);
+        assert_eq!(std::fs::read(dir.join("a")).unwrap(), DATA);
+    }
+
+    #[test]
+    fn fails_on_checksum_mismatch() {
+        let dir = temp_dir("mismatch");
+        let server = TestServer::with_files(vec![("/a", DATA.to_vec())]);
+        let task = DownloadTask::new(server.url("/a"), dir.join("a"))
+            .with_sha1(Some("0000000000000000000000000000000000000000"));
+        assert!(matches!(download(&Client::new(), &task, 2, &CancelToken::new()), Err(CoreError::Checksum(_))));
+        assert_eq!(server.requests().len(), 3);
+        assert!(!dir.join("a").exists());
+    }
+
+    #[test]
+    fn stops_when_cancelled() {
+        let dir = temp_dir("cancel");
+        let server = TestServer::with_files(vec![("/a", DATA.to_vec())]);
+        let task = DownloadTask::new(server.url("/a"), dir.join("a"));
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert!(matches!(download(&Client::new(), &task, 0, &cancel), Err(CoreError::Cancelled)));
+        assert!(server.requests().is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::{Display, Formatter};
+
+// 错误类型
+#[derive(Debug)]
+pub enum CoreError {
+    // 网络请求失败
+    Network(reqwest::Error),
+    // 文件读写失败
+    Io(std::io::Error),
+    // Json格式不正确或缺少字段
+    Manifest(String),
+    // 找不到版本
+    MissingVersion(String),
+    // 找不到inheritsFrom指向的版本
+    MissingInheritsFrom(String),
+    // inheritsFrom形成了循环，参数为循环的版本链
+    InheritsFromCycle(Vec<String>),
+    // 找不到实例
+    MissingInstance(String),
+    // 实例名字不能为空
+    InvalidInstanceName(String),
+    // 爪哇相关的错误
+    Java(String),
+    // JVM参数互相冲突
+    JvmArguments(String),
+    // 没有符合版本要求的爪哇，参数为需要的主版本号
+    NoCompatibleJava(u32),
+    // 安装器的处理器运行失败
+    Installer(String),
+    // 文件校验失败
+    Checksum(String),
+    // 有文件下载失败，参数为文件路径和原因
+    DownloadFailed(Vec<String>),
+    // 登录失败
+    Auth(String),
+    // 凭据库无法解密（密钥不对或被篡改）
+    Vault(String),
+    // 启动参数里有不认识的${...}变量
+    UnknownPlaceholders(Vec<String>),
+    // 用户取消
+    Cancelled,
+}
+
+pub type Result<T> = std::result::Result<T, CoreError>;
+
+impl CoreError {
+    // 是否值得重试
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, CoreError::Network(_) | CoreError::Io(_) | CoreError::Checksum(_))
+    }
+}
+
+impl Display for CoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoreError::Network(e) => write!(f, "Network error: {}", e),
+            CoreError::Io(e) => write!(f, "IO error: {}", e),
+            CoreError::Manifest(e) => write!(f, "Manifest error: {}", e),
+            CoreError::MissingVersion(id) => write!(f, "Could not find version \"{}\"!", id),
+            CoreError::MissingInheritsFrom(id) => write!(f, "Could not find inheritsFrom \"{}\" for version!", id),
+            CoreError::InheritsFromCycle(chain) => write!(f, "Cyclic inheritsFrom: {}", chain.join(" -> ")),
+            CoreError::MissingInstance(id) => write!(f, "Could not find instance \"{}\"!", id),
+            CoreError::InvalidInstanceName(name) => write!(f, "Invalid instance name \"{}\"!", name),
+            CoreError::Java(e) => write!(f, "Java error: {}", e),
+            CoreError::JvmArguments(e) => write!(f, "Invalid JVM arguments: {}", e),
+            CoreError::NoCompatibleJava(major) if *major <= 8 => write!(f, "No compatible Java found, Java {} is required!", major),
+            CoreError::NoCompatibleJava(major) => write!(f, "No compatible Java found, Java {} or newer is required!", major),
+            CoreError::Installer(e) => write!(f, "Installer error: {}", e),
+            CoreError::Checksum(e) => write!(f, "Checksum mismatch: {}", e),
+            CoreError::DownloadFailed(files) => write!(f, "{} files could not be downloaded: {}", files.len(), files.join("; ")),
+            CoreError::Auth(e) => write!(f, "Authentication failed: {}", e),
+            CoreError::Vault(e) => write!(f, "Credential vault error: {}", e),
+            CoreError::UnknownPlaceholders(keys) => write!(f, "Unknown placeholders in arguments: {}", keys.join(", ")),
+            CoreError::Cancelled => write!(f, "Cancelled!"),
+        }
+    }
+}
+
+impl std::error::Error for CoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CoreError::Network(e) => Some(e),
+            CoreError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for CoreError {
+    fn from(e: reqwest::Error) -> Self {
+        CoreError::Network(e)
+    }
+}
+
+impl From<std::io::Error> for CoreError {
+    fn from(e: std::io::Error) -> Self {
+        CoreError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for CoreError {
+    fn from(e: serde_json::Error) -> Self {
+        CoreError::Manifest(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for CoreError {
+    fn from(e: zip::result::ZipError) -> Self {
+        CoreError::Io(e.into())
+    }
+}
+
+// 从Json中取字段的辅助函数
+pub(crate) fn missing(key: &str) -> CoreError {
+    CoreError::Manifest(format!("Missing or invalid key \"{}\"!", key))
+}
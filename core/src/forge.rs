@@ -0,0 +1,655 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env::temp_dir;
+use std::fs::{create_dir_all, remove_dir_all, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use zip::ZipArchive;
+use crate::download::{file_sha1, DownloadTask};
+use crate::error::missing;
+use crate::install::library_jobs;
+use crate::maven::MavenCoordinate;
+use crate::model::{Library, Version};
+use crate::progress::{CancelToken, InstallPhase, ProgressSink, Tracker};
+use crate::scheduler::Scheduler;
+use crate::source::{DownloadSources, FORGE_MAVEN, NEOFORGE_MAVEN};
+use crate::{CoreError, Result};
+
+// 旧版Json里的Forge仓库已经不能用了
+const LEGACY_FORGE_MAVEN: &str = "http://files.minecraftforge.net/maven";
+
+// 使用安装器安装的模组加载器
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForgeKind {
+    Forge,
+    NeoForge,
+}
+
+impl ForgeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForgeKind::Forge => "Forge",
+            ForgeKind::NeoForge => "NeoForge",
+        }
+    }
+
+    fn maven(&self) -> &'static str {
+        match self {
+            ForgeKind::Forge => FORGE_MAVEN,
+            ForgeKind::NeoForge => NEOFORGE_MAVEN,
+        }
+    }
+
+    // 安装器的坐标，Forge的版本号包括游戏版本，例如1.20.1-47.1.0
+    pub fn installer(&self, version: &str) -> MavenCoordinate {
+        let (group, artifact) = match self {
+            ForgeKind::Forge => ("net.minecraftforge", "forge"),
+            // 1.20.1的NeoForge沿用Forge的名字和版本号，例如1.20.1-47.1.106
+            ForgeKind::NeoForge if version.starts_with("1.") => ("net.neoforged", "forge"),
+            ForgeKind::NeoForge => ("net.neoforged", "neoforge"),
+        };
+        MavenCoordinate {
+            group: String::from(group),
+            artifact: String::from(artifact),
+            version: String::from(version),
+            classifier: Some(String::from("installer")),
+            extension: String::from("jar"),
+        }
+    }
+
+    pub fn installer_url(&self, version: &str) -> String {
+        self.installer(version).url(self.maven())
+    }
+}
+
+// 1.13以后的install_profile.json
+#[derive(Deserialize)]
+struct InstallProfile {
+    minecraft: String,
+    // 安装器里版本Json的路径
+    #[serde(default = "default_json")]
+    json: String,
+    #[serde(default)]
+    data: BTreeMap<String, DataEntry>,
+    #[serde(default)]
+    processors: Vec<Processor>,
+    #[serde(default)]
+    libraries: Vec<Library>,
+}
+
+fn default_json() -> String {
+    String::from("/version.json")
+}
+
+#[derive(Deserialize)]
+struct DataEntry {
+    client: String,
+}
+
+#[derive(Deserialize)]
+struct Processor {
+    // 没有时客户端和服务端都要运行
+    sides: Option<Vec<String>>,
+    jar: String,
+    #[serde(default)]
+    classpath: Vec<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    // 输出文件 => sha1
+    #[serde(default)]
+    outputs: BTreeMap<String, String>,
+}
+
+// 1.13以前的install_profile.json
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacyProfile {
+    install: LegacyInstall,
+    version_info: Version,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacyInstall {
+    // universal文件的坐标
+    path: String,
+    // universal文件在安装器里的文件名
+    file_path: String,
+    minecraft: String,
+}
+
+fn read_entry(zip: &mut ZipArchive<File>, name: &str) -> Result<String> {
+    let mut text = String::new();
+    zip.by_name(name).map_err(|_| missing(name))?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+fn extract_entry(zip: &mut ZipArchive<File>, name: &str, path: &Path) -> Result<()> {
+    let mut entry = zip.by_name(name).map_err(|_| missing(name))?;
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    io::copy(&mut entry, &mut File::create(path)?)?;
+    Ok(())
+}
+
+// 安装器给出的相对路径不能跳出目标目录
+fn enclosed(base: &Path, relative: &str) -> Result<PathBuf> {
+    let path = Path::new(relative);
+    if relative.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(CoreError::Installer(format!("Path \"{}\" escapes {}!", relative, base.display())));
+    }
+    Ok(base.join(path))
+}
+
+fn library_path(library_dir: &Path, name: &str) -> Result<String> {
+    Ok(enclosed(library_dir, &MavenCoordinate::parse(name)?.path())?.to_string_lossy().into_owned())
+}
+
+// 替换参数里的{KEY}
+fn replace_tokens(text: &str, variables: &HashMap<String, String>) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|e| start + e) else { break };
+        let key = &rest[start + 1..end];
+        let value = variables.get(key)
+            .ok_or_else(|| CoreError::Manifest(format!("Unknown processor variable \"{}\"!", key)))?;
+        out.push_str(&rest[..start]);
+        out.push_str(value);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+// 处理器参数：[坐标]是依赖库文件，其余的替换变量
+fn processor_arg(arg: &str, variables: &HashMap<String, String>, library_dir: &Path) -> Result<String> {
+    match arg.strip_prefix('[').and_then(|a| a.strip_suffix(']')) {
+        Some(name) => library_path(library_dir, name),
+        None => replace_tokens(arg, variables),
+    }
+}
+
+// 从处理器的MANIFEST里读取主类
+fn main_class(jar: &Path) -> Result<String> {
+    let mut zip = ZipArchive::new(File::open(jar)?)?;
+    let manifest = read_entry(&mut zip, "META-INF/MANIFEST.MF")?;
+    manifest.lines()
+        .find_map(|l| l.strip_prefix("Main-Class:"))
+        .map(|c| String::from(c.trim()))
+        .ok_or_else(|| CoreError::Installer(format!("{} has no Main-Class!", jar.display())))
+}
+
+// 一次安装用到的路径和状态
+struct Context<'a> {
+    installer: &'a Path,
+    dir: &'a Path,
+    java: &'a Path,
+    tracker: &'a Tracker,
+    cancel: &'a CancelToken,
+}
+
+pub struct ForgeInstaller {
+    sources: DownloadSources,
+    client: Client,
+    pool_size: usize,
+}
+
+impl Default for ForgeInstaller {
+    fn default() -> Self {
+        ForgeInstaller::new()
+    }
+}
+
+impl ForgeInstaller {
+    pub fn new() -> ForgeInstaller {
+        ForgeInstaller {
+            sources: DownloadSources::official(),
+            client: Client::new(),
+            pool_size: 8,
+        }
+    }
+
+    pub fn with_sources(mut self, sources: DownloadSources) -> ForgeInstaller {
+        self.sources = sources;
+        self
+    }
+
+    pub fn with_client(mut self, client: Client) -> ForgeInstaller {
+        self.client = client;
+        self
+    }
+
+    pub fn with_pool_size(mut self, pool_size: usize) -> ForgeInstaller {
+        self.pool_size = pool_size;
+        self
+    }
+
+    fn scheduler(&self) -> Scheduler {
+        Scheduler::new(self.client.clone(), self.sources.clone()).with_pool_size(self.pool_size)
+    }
+
+    // 下载安装器到path
+    pub fn download_installer(
+        &self,
+        kind: ForgeKind,
+        version: &str,
+        path: &Path,
+        progress: Arc<dyn ProgressSink>,
+        cancel: &CancelToken) -> Result<()> {
+        let tracker = Tracker::new(progress);
+        tracker.phase(InstallPhase::Json);
+        self.scheduler().run_one(DownloadTask::new(kind.installer_url(version), path), &tracker, cancel)
+    }
+
+    // 下载依赖库，有失败的文件时返回第一个错误
+    fn download_libraries(&self, libraries: &[Library], library_dir: &Path, tracker: &Tracker, cancel: &CancelToken) -> Result<()> {
+        tracker.phase(InstallPhase::Libraries);
+        let failed = self.scheduler().run(library_jobs(libraries, library_dir)?, tracker, cancel)?;
+        match failed.into_iter().next() {
+            Some((_, e)) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    // 使用安装器安装，java用来运行处理器，返回新版本的ID，原版需要已经安装
+    pub fn install(
+        &self,
+        installer: &Path,
+        dir: &Path,
+        java: &Path,
+        progress: Arc<dyn ProgressSink>,
+        cancel: &CancelToken) -> Result<String> {
+        let tracker = Tracker::new(progress);
+        tracker.phase(InstallPhase::Json);
+        cancel.check()?;
+        let mut zip = ZipArchive::new(File::open(installer)?)?;
+        let profile: Value = serde_json::from_str(&read_entry(&mut zip, "install_profile.json")?)?;
+        let context = Context { installer, dir, java, tracker: &tracker, cancel };
+        let id = if profile.get("install").is_some() && profile.get("versionInfo").is_some() {
+            self.install_legacy(serde_json::from_value(profile)?, &mut zip, &context)?
+        } else {
+            self.install_modern(serde_json::from_value(profile)?, &mut zip, &context)?
+        };
+        tracker.phase(InstallPhase::Done);
+        Ok(id)
+    }
+
+    // 1.13以前：解压universal文件，versionInfo就是版本Json
+    fn install_legacy(
+        &self,
+        profile: LegacyProfile,
+        zip: &mut ZipArchive<File>,
+        context: &Context) -> Result<String> {
+        let minecraft = &profile.install.minecraft;
+        let versions_dir = context.dir.join("versions");
+        if !versions_dir.join(minecraft).join(format!("{}.json", minecraft)).is_file() {
+            return Err(CoreError::MissingInheritsFrom(minecraft.clone()));
+        }
+        let library_dir = context.dir.join("libraries");
+        let universal = library_path(&library_dir, &profile.install.path)?;
+        extract_entry(zip, &profile.install.file_path, Path::new(&universal))?;
+
+        let mut version = profile.version_info;
+        // 更早的版本包含了原版的全部内容，同样改为继承原版
+        if version.inherits_from.is_none() {
+            version.inherits_from = Some(minecraft.clone());
+            version.extra.entry("jar").or_insert_with(|| Value::from(minecraft.as_str()));
+        }
+        for library in &mut version.libraries {
+            if let Some(url) = &library.url {
+                library.url = Some(url.replace(LEGACY_FORGE_MAVEN, FORGE_MAVEN));
+            }
+        }
+        let version_dir = enclosed(&versions_dir, &version.id)?;
+        create_dir_all(&version_dir)?;
+        let mut file = File::create(version_dir.join(format!("{}.json", version.id)))?;
+        file.write_all(serde_json::to_string_pretty(&version)?.as_bytes())?;
+        file.flush()?;
+
+        // clientreq为false的库只有服务端需要
+        let libraries: Vec<Library> = version.libraries.iter()
+            .filter(|l| l.extra.get("clientreq") != Some(&Value::Bool(false)))
+            .cloned()
+            .collect();
+        self.download_libraries(&libraries, &library_dir, context.tracker, context.cancel)?;
+        Ok(version.id)
+    }
+
+    // 1.13以后：下载依赖库，运行处理器生成游戏需要的文件
+    fn install_modern(&self, profile: InstallProfile, zip: &mut ZipArchive<File>, context: &Context) -> Result<String> {
+        let minecraft = &profile.minecraft;
+        let versions_dir = context.dir.join("versions");
+        let minecraft_dir = versions_dir.join(minecraft);
+        let minecraft_jar = minecraft_dir.join(format!("{}.jar", minecraft));
+        // 处理器需要原版的游戏主文件
+        if !minecraft_dir.join(format!("{}.json", minecraft)).is_file() || !minecraft_jar.is_file() {
+            return Err(CoreError::MissingInheritsFrom(minecraft.clone()));
+        }
+        let json = read_entry(zip, profile.json.trim_start_matches('/'))?;
+        let version: Version = serde_json::from_str(&json)?;
+        let version_dir = enclosed(&versions_dir, &version.id)?;
+
+        // 安装器自带的依赖库
+        let library_dir = context.dir.join("libraries");
+        for i in 0..zip.len() {
+            let name = String::from(zip.by_index(i)?.name());
+            if let Some(path) = name.strip_prefix("maven/").filter(|p| !p.is_empty() && !p.ends_with('/')) {
+                extract_entry(zip, &name, &enclosed(&library_dir, path)?)?;
+            }
+        }
+        let mut libraries = profile.libraries.clone();
+        libraries.extend(version.libraries.iter().cloned());
+        self.download_libraries(&libraries, &library_dir, context.tracker, context.cancel)?;
+
+        context.tracker.phase(InstallPhase::Processors);
+        let temp = temp_dir().join(format!("akiraka-forge-{}-{}", version.id, std::process::id()));
+        let result = self.run_processors(&profile, zip, context, &minecraft_jar, &temp);
+        let _ = remove_dir_all(&temp);
+        result?;
+
+        create_dir_all(&version_dir)?;
+        let mut file = File::create(version_dir.join(format!("{}.json", version.id)))?;
+        file.write_all(json.as_bytes())?;
+        file.flush()?;
+        Ok(version.id)
+    }
+
+    // 处理器可以使用的变量
+    fn variables(
+        &self,
+        profile: &InstallProfile,
+        zip: &mut ZipArchive<File>,
+        context: &Context,
+        minecraft_jar: &Path,
+        temp: &Path) -> Result<HashMap<String, String>> {
+        let library_dir = context.dir.join("libraries");
+        let mut variables = HashMap::new();
+        for (key, entry) in &profile.data {
+            let value = &entry.client;
+            let value = if let Some(name) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                library_path(&library_dir, name)?
+            } else if let Some(literal) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+                String::from(literal)
+            } else if let Some(name) = value.strip_prefix('/') {
+                // 安装器里的文件，解压到临时目录
+                let path = enclosed(temp, name)?;
+                extract_entry(zip, name, &path)?;
+                path.to_string_lossy().into_owned()
+            } else {
+                value.clone()
+            };
+            variables.insert(key.clone(), value);
+        }
+        variables.insert(String::from("SIDE"), String::from("client"));
+        variables.insert(String::from("MINECRAFT_JAR"), minecraft_jar.to_string_lossy().into_owned());
+        variables.insert(String::from("MINECRAFT_VERSION"), profile.minecraft.clone());
+        variables.insert(String::from("ROOT"), context.dir.to_string_lossy().into_owned());
+        variables.insert(String::from("INSTALLER"), context.installer.to_string_lossy().into_owned());
+        variables.insert(String::from("LIBRARY_DIR"), library_dir.to_string_lossy().into_owned());
+        Ok(variables)
+    }
+
+    fn run_processors(
+        &self,
+        profile: &InstallProfile,
+        zip: &mut ZipArchive<File>,
+        context: &Context,
+        minecraft_jar: &Path,
+        temp: &Path) -> Result<()> {
+        let variables = self.variables(profile, zip, context, minecraft_jar, temp)?;
+        let library_dir = context.dir.join("libraries");
+        let path_separator = if cfg!(windows) { ";" } else { ":" };
+        for processor in &profile.processors {
+            context.cancel.check()?;
+            if !processor.sides.as_ref().is_none_or(|s| s.iter().any(|s| s == "client")) {
+                continue;
+            }
+            let mut outputs = Vec::new();
+            for (path, sha1) in &processor.outputs {
+                let sha1 = replace_tokens(sha1, &variables)?;
+                outputs.push((PathBuf::from(replace_tokens(path, &variables)?), String::from(sha1.trim_matches('\''))));
+            }
+            // 输出文件都已经正确时跳过
+            let finished = !outputs.is_empty() && outputs.iter()
+                .all(|(path, sha1)| path.is_file() && file_sha1(path).is_ok_and(|s| s.eq_ignore_ascii_case(sha1)));
+            if finished {
+                continue;
+            }
+
+            let jar = PathBuf::from(library_path(&library_dir, &processor.jar)?);
+            let mut classpath = vec![jar.to_string_lossy().into_owned()];
+            for name in &processor.classpath {
+                classpath.push(library_path(&library_dir, name)?);
+            }
+            let mut args = Vec::new();
+            for arg in &processor.args {
+                args.push(processor_arg(arg, &variables, &library_dir)?);
+            }
+            let output = Command::new(context.java)
+                .arg("-cp")
+                .arg(classpath.join(path_separator))
+                .arg(main_class(&jar)?)
+                .args(&args)
+                .output()
+                .map_err(|e| CoreError::Java(format!("Could not execute processor {}: {}", processor.jar, e)))?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(CoreError::Installer(format!("Processor {} failed ({}): {}", processor.jar, output.status, stderr.trim())));
+            }
+
+            for (path, sha1) in &outputs {
+                let actual = file_sha1(path)?;
+                if !actual.eq_ignore_ascii_case(sha1) {
+                    return Err(CoreError::Checksum(format!("{}: expected {}, got {}", path.display(), sha1, actual)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{copy, read, read_to_string};
+    use serde_json::json;
+    use sha1::{Digest, Sha1};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+    use crate::progress::NoProgress;
+    use crate::resolver::resolve_version;
+    use crate::source::Mirror;
+    use crate::test_server::{Response, TestServer};
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+    }
+
+    // 安装了原版的游戏目录
+    fn game_dir(name: &str, minecraft: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("akiraka-forge-{}-{}", name, std::process::id()));
+        let _ = remove_dir_all(&dir);
+        let version_dir = dir.join("versions").join(minecraft);
+        create_dir_all(&version_dir).unwrap();
+        copy(fixtures().join("versions").join(minecraft).join(format!("{}.json", minecraft)), version_dir.join(format!("{}.json", minecraft))).unwrap();
+        std::fs::write(version_dir.join(format!("{}.jar", minecraft)), "client").unwrap();
+        dir
+    }
+
+    fn write_installer(path: &Path, entries: &[(&str, Vec<u8>)]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn sha1_hex(data: &[u8]) -> String {
+        Sha1::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn maven_server() -> TestServer {
+        TestServer::start(|req| if req.path.ends_with(".jar") { Response::ok("jar") } else { Response::not_found() })
+    }
+
+    fn maven_sources(maven: &TestServer) -> DownloadSources {
+        DownloadSources::new(vec![Arc::new(Mirror::new("Test")
+            .with_rule(FORGE_MAVEN, maven.url("/forge"))
+            .with_rule("https://libraries.minecraft.net", maven.url("/mojang")))])
+    }
+
+    // 处理器把BINPATCH复制为客户端文件，再把原版主文件复制到游戏目录
+    fn modern_installer(path: &Path, patched_sha1: &str) {
+        let profile = json!({
+            "spec": 1,
+            "profile": "forge",
+            "version": "1.20.1-forge-47.1.0",
+            "minecraft": "1.20.1",
+            "json": "/version.json",
+            "data": {
+                "BINPATCH": {"client": "/data/client.lzma", "server": "/data/server.lzma"},
+                "PATCHED": {"client": "[net.minecraftforge:forge:1.20.1-47.1.0:client]", "server": "[net.minecraftforge:forge:1.20.1-47.1.0:server]"},
+                "PATCHED_SHA": {"client": format!("'{}'", patched_sha1), "server": "''"}
+            },
+            "processors": [
+                {"sides": ["server"], "jar": "net.minecraftforge:missing:1.0", "args": []},
+                {"jar": "akiraka:copy:1.0", "args": ["{BINPATCH}", "{PATCHED}"], "outputs": {"{PATCHED}": "{PATCHED_SHA}"}},
+                {"sides": ["client"], "jar": "akiraka:copy:1.0", "args": ["{MINECRAFT_JAR}", "{ROOT}/copied-{SIDE}.jar"]}
+            ],
+            "libraries": [
+                {"name": "akiraka:copy:1.0", "downloads": {"artifact": {"path": "akiraka/copy/1.0/copy-1.0.jar", "url": ""}}},
+                {"name": "net.minecraftforge:installertools:1.3.0", "url": "https://maven.minecraftforge.net/"}
+            ]
+        });
+        let version = json!({
+            "id": "1.20.1-forge-47.1.0",
+            "inheritsFrom": "1.20.1",
+            "type": "release",
+            "mainClass": "cpw.mods.bootstraplauncher.BootstrapLauncher",
+            "libraries": [
+                {"name": "net.minecraftforge:forge:1.20.1-47.1.0:universal", "downloads": {"artifact": {"path": "net/minecraftforge/forge/1.20.1-47.1.0/forge-1.20.1-47.1.0-universal.jar", "url": ""}}},
+                {"name": "net.minecraftforge:forge:1.20.1-47.1.0:client", "downloads": {"artifact": {"path": "net/minecraftforge/forge/1.20.1-47.1.0/forge-1.20.1-47.1.0-client.jar", "url": ""}}},
+                {"name": "cpw.mods:securejarhandler:2.1.10", "downloads": {"artifact": {"path": "cpw/mods/securejarhandler/2.1.10/securejarhandler-2.1.10.jar", "url": "https://maven.minecraftforge.net/cpw/mods/securejarhandler/2.1.10/securejarhandler-2.1.10.jar"}}}
+            ]
+        });
+        write_installer(path, &[
+            ("install_profile.json", profile.to_string().into_bytes()),
+            ("version.json", version.to_string().into_bytes()),
+            ("data/client.lzma", b"patched".to_vec()),
+            ("maven/akiraka/copy/1.0/copy-1.0.jar", read(fixtures().join("forge/copy.jar")).unwrap()),
+            ("maven/net/minecraftforge/forge/1.20.1-47.1.0/forge-1.20.1-47.1.0-universal.jar", b"universal".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn runs_processors() {
+        let maven = maven_server();
+        let dir = game_dir("modern", "1.20.1");
+        let installer = dir.join("installer.jar");
+        modern_installer(&installer, &sha1_hex(b"patched"));
+
+        let id = ForgeInstaller::new().with_sources(maven_sources(&maven))
+            .install(&installer, &dir, Path::new("java"), Arc::new(NoProgress), &CancelToken::new())
+            .unwrap();
+        assert_eq!(id, "1.20.1-forge-47.1.0");
+        let forge_dir = dir.join("libraries/net/minecraftforge/forge/1.20.1-47.1.0");
+        assert_eq!(read_to_string(forge_dir.join("forge-1.20.1-47.1.0-client.jar")).unwrap(), "patched");
+        assert_eq!(read_to_string(forge_dir.join("forge-1.20.1-47.1.0-universal.jar")).unwrap(), "universal");
+        assert_eq!(read_to_string(dir.join("copied-client.jar")).unwrap(), "client");
+        assert!(dir.join("libraries/cpw/mods/securejarhandler/2.1.10/securejarhandler-2.1.10.jar").is_file());
+        assert!(maven.requests().contains(&String::from("GET /forge/net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar")));
+
+        let resolved = resolve_version(&dir.join("versions"), &id).unwrap();
+        assert_eq!(resolved.jar(), "1.20.1");
+        assert_eq!(resolved.version.main_class.as_deref(), Some("cpw.mods.bootstraplauncher.BootstrapLauncher"));
+
+        // 输出文件的校验值不对
+        modern_installer(&installer, &sha1_hex(b"unpatched"));
+        assert!(matches!(
+            ForgeInstaller::new().with_sources(maven_sources(&maven))
+                .install(&installer, &dir, Path::new("java"), Arc::new(NoProgress), &CancelToken::new()),
+            Err(CoreError::Checksum(_))
+        ));
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_game_dir() {
+        let maven = maven_server();
+        let dir = game_dir("traversal", "1.20.1");
+        let installer = dir.join("installer.jar");
+        let profile = json!({"minecraft": "1.20.1", "json": "/version.json"});
+        let version = json!({"id": "1.20.1-forge-47.1.0", "inheritsFrom": "1.20.1", "type": "release", "libraries": []});
+        write_installer(&installer, &[
+            ("install_profile.json", profile.to_string().into_bytes()),
+            ("version.json", version.to_string().into_bytes()),
+            ("maven/../../evil", b"evil".to_vec()),
+        ]);
+        assert!(matches!(
+            ForgeInstaller::new().with_sources(maven_sources(&maven))
+                .install(&installer, &dir, Path::new("java"), Arc::new(NoProgress), &CancelToken::new()),
+            Err(CoreError::Installer(_))
+        ));
+        assert!(!dir.join("evil").exists());
+        assert!(!dir.parent().unwrap().join("evil").exists());
+
+        let temp = Path::new("/tmp");
+        assert!(enclosed(temp, "data/client.lzma").is_ok());
+        assert!(enclosed(temp, "data/../../client.lzma").is_err());
+        assert!(enclosed(temp, "/etc/passwd").is_err());
+        assert!(library_path(temp, "a:..:1.0").is_err());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn installs_legacy_universal() {
+        let maven = maven_server();
+        let dir = game_dir("legacy", "1.12.2");
+        let installer = dir.join("installer.jar");
+        write_installer(&installer, &[
+            ("install_profile.json", read(fixtures().join("forge/legacy-install-profile.json")).unwrap()),
+            ("forge-1.12.2-14.23.5.2860-universal.jar", b"universal".to_vec()),
+        ]);
+
+        let id = ForgeInstaller::new().with_sources(maven_sources(&maven))
+            .install(&installer, &dir, Path::new("java"), Arc::new(NoProgress), &CancelToken::new())
+            .unwrap();
+        assert_eq!(id, "1.12.2-forge-14.23.5.2860");
+        let library_dir = dir.join("libraries");
+        assert_eq!(read_to_string(library_dir.join("net/minecraftforge/forge/1.12.2-14.23.5.2860/forge-1.12.2-14.23.5.2860.jar")).unwrap(), "universal");
+        assert!(library_dir.join("org/ow2/asm/asm-all/5.2/asm-all-5.2.jar").is_file());
+        assert!(library_dir.join("net/minecraft/launchwrapper/1.12/launchwrapper-1.12.jar").is_file());
+        // 只有服务端需要的库不下载
+        assert!(!library_dir.join("jline/jline/2.13/jline-2.13.jar").exists());
+        assert!(maven.requests().contains(&String::from("GET /mojang/lzma/lzma/0.0.1/lzma-0.0.1.jar")));
+
+        let resolved = resolve_version(&dir.join("versions"), &id).unwrap();
+        assert_eq!(resolved.jar(), "1.12.2");
+        assert_eq!(resolved.version.main_class.as_deref(), Some("net.minecraft.launchwrapper.Launch"));
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replaces_processor_variables() {
+        let variables = HashMap::from([
+            (String::from("SIDE"), String::from("client")),
+            (String::from("ROOT"), String::from("/game")),
+        ]);
+        assert_eq!(replace_tokens("{ROOT}/copied-{SIDE}.jar", &variables).unwrap(), "/game/copied-client.jar");
+        assert_eq!(processor_arg("--side", &variables, Path::new("/lib")).unwrap(), "--side");
+        assert_eq!(
+            processor_arg("[net.minecraft:client:1.20.1-20230612.114412:slim]", &variables, Path::new("/lib")).unwrap(),
+            Path::new("/lib").join("net/minecraft/client/1.20.1-20230612.114412/client-1.20.1-20230612.114412-slim.jar").to_string_lossy()
+        );
+        assert!(replace_tokens("{MISSING}", &variables).is_err());
+        assert_eq!(ForgeKind::NeoForge.installer_url("20.4.80-beta"), "https://maven.neoforged.net/releases/net/neoforged/neoforge/20.4.80-beta/neoforge-20.4.80-beta-installer.jar");
+        assert_eq!(ForgeKind::NeoForge.installer_url("1.20.1-47.1.106"), "https://maven.neoforged.net/releases/net/neoforged/forge/1.20.1-47.1.106/forge-1.20.1-47.1.106-installer.jar");
+    }
+}
@@ -1,192 +1,298 @@
-use std::env::consts::OS;
-use std::fs::{create_dir_all, File};
-use std::io::{Read, Write};
-use std::path::{Path};
+use std::fs::{create_dir_all, read_to_string, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
-use reqwest::blocking::Client;
-use serde_json::Value;
-use zip::ZipArchive;
-use crate::core::{check_rule, VersionSource};
+use crate::download::DownloadTask;
+use crate::error::missing;
+use crate::progress::{CancelToken, InstallPhase, Progress, ProgressSink, Tracker};
+use crate::scheduler::{Job, Scheduler};
+use crate::source::{DownloadSources, RESOURCES};
+use crate::{CoreError, Result, VersionSource};
+use crate::model::{AssetIndex, AssetObject, Library, Version};
+use crate::natives::{resolve, NativeKind};
+use crate::rule::{is_allowed, Features, Platform};
 
-const ASSETS_URL: &str = "https://resources.download.minecraft.net/";
+// 下载依赖库的任务，没有downloads时从Maven仓库下载
+pub(crate) fn library_jobs(libraries: &[Library], library_path: &Path) -> Result<Vec<Job>> {
+    let mut jobs = Vec::new();
+    for library in libraries {
+        // 检查是否包含规则
+        if !is_allowed(library.rules.as_deref(), Platform::current(), &Features::default()) {
+            continue;
+        }
 
+        let Some(artifact) = library.artifact_download()? else {
+            continue
+        };
+        // 地址为空的文件由安装器生成
+        if artifact.url.is_empty() {
+            continue;
+        }
+        let file_path = match &artifact.path {
+            Some(path) => library_path.join(path),
+            None => library_path.join(library.coordinate()?.path()),
+        };
+        let task = DownloadTask::from_download(&artifact, file_path);
+        // 没有校验信息的文件已经存在时不再下载
+        if task.is_verifiable() || !task.path.is_file() {
+            jobs.push(Job::new(task));
+        }
+    }
+    Ok(jobs)
+}
+
+// 资源在objects目录和资源服务器上的相对路径：<前两位>/<SHA-1>
+fn object_path(hash: &str) -> Result<String> {
+    if hash.len() != 40 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(missing("objects.hash"));
+    }
+    Ok(format!("{}/{}", &hash[0..2], hash))
+}
+
+// 安装原版游戏，所有文件下载完成后才返回最终的进度，有文件下载失败时返回DownloadFailed
 pub fn install(
     source: &VersionSource,
+    sources: &DownloadSources,
     dir: String,
     time_out: usize,
-    // TODO: 多线程
-    pool_size: usize) -> Result<(), std::io::Error> {
+    pool_size: usize,
+    progress: Arc<dyn ProgressSink>,
+    cancel: &CancelToken) -> Result<Progress> {
     // 御坂美琴生日快乐！
-
-    // 下崽函数
-    fn download(url: &str, mut file: &File, client: &Client) {
-        file.write_all(&client.get(url).send().unwrap().bytes().unwrap()).expect("Could not write library file!");
-        file.flush().expect("Could not flush file!");
-    }
-
-    // 下崽并解鸭函数
-    fn download_and_extract(url: &str, mut file: File, extract_path: &Path, client: Client) {
-        create_dir_all(extract_path).expect("Could not crate natives directory!");
-        let buf = &client.get(url).send().unwrap().bytes().unwrap();
-        file.write_all(buf).expect("Could not write library file!");
-        file.flush().expect("Could not flush file!");
-
-        let contents = buf.to_vec();
-        let mut zip_archive = ZipArchive::new(std::io::Cursor::new(&contents[..])).expect("Could not open Native library as Zip file!");
-        for i in 0..zip_archive.len() {
-            let mut buf = zip_archive.by_index(i).expect("Could not extract Native library!");
-            if buf.is_dir() {
-                create_dir_all(extract_path.clone().join(buf.name())).expect("Could not crate directory!");
-            } else if buf.is_file() {
-                let mut file = File::create(extract_path.clone().join(buf.name())).expect("Could not create extracted file!");
-                let mut extracted = Vec::new();
-                buf.read_to_end(&mut extracted).expect("Could not read zip file!");
-                file.write_all(&extracted[..]).expect("Could not write to extracted file!");
-                file.flush().expect("Could not flush extracted file!");
-            }
-        }
-    }
+    let tracker = Tracker::new(progress);
 
     // Http客户端
-    let client = reqwest::blocking::ClientBuilder::new().timeout(Duration::from_millis(time_out as u64)).build().expect("Could not create a Http Client!");
+    let client = reqwest::blocking::ClientBuilder::new().timeout(Duration::from_millis(time_out as u64)).build()?;
+    // 下载调度器
+    let scheduler = Scheduler::new(client.clone(), sources.clone()).with_pool_size(pool_size);
 
     // 目录
-    create_dir_all(dir.clone()).expect("Could not create directory!");
+    create_dir_all(dir.clone())?;
     let launcher_profiles_path = Path::new(dir.as_str()).join("launcher_profiles.json");
     if !launcher_profiles_path.exists() {
-        let mut launcher_profiles = File::create(launcher_profiles_path).expect("Could not create Launcher Profiles!");
-        launcher_profiles.write(String::from("{}").as_bytes()).expect("Could not write to Launcher Profiles!");
-        launcher_profiles.flush().expect("Could not flush Launcher Profiles!");
+        let mut launcher_profiles = File::create(launcher_profiles_path)?;
+        launcher_profiles.write_all(b"{}")?;
+        launcher_profiles.flush()?;
     }
-    let binding = Path::new(String::from(&dir).as_str()).join("assets");
+    let binding = Path::new(dir.as_str()).join("assets");
     let assets_path = binding.as_path();
-    let binding = Path::new(String::from(&dir).as_str()).join("libraries");
+    let binding = Path::new(dir.as_str()).join("libraries");
     let library_path = binding.as_path();
-    let binding = Path::new(String::from(&dir).as_str()).join("versions").join(&source.version_id);
+    let binding = Path::new(dir.as_str()).join("versions").join(&source.version_id);
     let versions_path = binding.as_path();
-    let binding = versions_path.clone().join(String::from(&source.version_id) + ".json");
+    let binding = versions_path.join(String::from(&source.version_id) + ".json");
     let json_path = binding.as_path();
-    let binding = versions_path.clone().join("natives");
-    let natives_path = binding.as_path();
 
     // 版本Json
-    create_dir_all(versions_path).expect("Could not create json file!");
-    let mut json_file = File::create(json_path).expect("Could not create json file!");
-    // TODO: 下载源
-    let json = client.get(&source.version_url).send().expect("Errors while downloading Json!").text().expect("Errors while reading Json!");
-    json_file.write_all(json.as_ref()).expect("Could not write to Json!");
-
+    tracker.phase(InstallPhase::Json);
+    cancel.check()?;
+    create_dir_all(versions_path)?;
+    let json = sources.get_text(&client, &source.version_url)?;
     // 解析Json
-    let json: Value = serde_json::from_str(json.as_str()).unwrap();
-
-    // 线程池
-    let pool = rayon::ThreadPoolBuilder::new().num_threads(pool_size).build().expect("Could not create a Thread pool!");
-    pool.install(|| println!("Thread pool created!"));
+    let version: Version = serde_json::from_str(json.as_str())?;
+    let mut json_file = File::create(json_path)?;
+    json_file.write_all(json.as_ref())?;
 
     // 下载
     // 游戏主文件
-    let binding = versions_path.clone().join(String::from(&source.version_id) + ".jar");
-    let url = &json["downloads"]["client"]["url"].as_str().unwrap();
-    let file = File::create(binding.as_path()).expect("Could not create Main file!");
-    let url = String::from(*url);
-    let file = file.try_clone().unwrap();
-    let c = client.clone();
-    pool.spawn(move || {
-        println!("Downloading Main file: {}", url);
-        download(url.as_str(), &file, &c);
-    });
+    tracker.phase(InstallPhase::Client);
+    let binding = versions_path.join(String::from(&source.version_id) + ".jar");
+    let client_jar = version.downloads.get("client").ok_or_else(|| missing("downloads.client"))?;
+    let task = DownloadTask::from_download(client_jar, binding);
+    scheduler.run_one(task, &tracker, cancel)?;
 
     // 依赖库
-    for library in json["libraries"].as_array().unwrap() {
-        // 检查是否包含规则
-        let mut allow = true;
-        if library.get("rules").is_some() {
-            allow = check_rule(library.get("rules").unwrap().as_array().unwrap());
-        }
+    let libraries = library_jobs(&version.libraries, library_path)?;
+    tracker.phase(InstallPhase::Libraries);
+    // 失败的文件，全部下载完后一起报告
+    let mut failed = scheduler.run(libraries, &tracker, cancel)?;
 
-        if !allow {
-            continue;
-        }
-
-        // TODO: 下载源的支持
-        // 下载
-        // Artifact
-        if library["downloads"].get("artifact").is_some() {
-            let file_path = library_path.clone().join(library["downloads"]["artifact"]["path"].as_str().unwrap());
-            create_dir_all(file_path.clone().parent().unwrap()).expect("Could not create library path!");
-            let url = library["downloads"]["artifact"]["url"].as_str().unwrap();
-            let file = File::create(library_path.clone().join(library["downloads"]["artifact"]["path"].as_str().unwrap())).unwrap();
-            let url = String::from(url);
-            let file = file.try_clone().unwrap();
-            let c = client.clone();
-            pool.spawn(move || {
-                println!("Downloading Library: {}", url);
-                download(url.as_str(), &file, &c);
-            });
-        }
-        // TODO: 多线程下载以及下载源的支持
-        // Classifiers
-        if library["downloads"].get("classifiers").is_some() && library.get("natives").is_some() {
-            let natives = &library["natives"];
-            // 系统
-            let os = if OS == "macos" {
-                "osx"
-            } else {
-                OS
-            };
-            if natives.get(os).is_some() {
-                create_dir_all(natives_path).expect("Could not create natives directory!");
-                let natives = &library["downloads"]["classifiers"][&library["natives"][os].as_str().unwrap()];
-                if natives.is_null() {
-                    continue
-                }
-                let url = natives["url"].as_str().unwrap();
-                create_dir_all(library_path.clone().join(natives["path"].as_str().unwrap()).as_path().parent().unwrap()).expect("Could not create native directory!");
-                let file = File::create(library_path.clone().join(natives["path"].as_str().unwrap()).as_path()).expect("Could not create native library!");
-                println!("Downloading Natives: {}", url);
-                download_and_extract(url, file.try_clone().unwrap(), natives_path, client.clone());
-            }
+    // Native库
+    // 旧格式的Native库在classifiers里，新格式的已经作为依赖库下载，启动时再解压
+    tracker.phase(InstallPhase::Natives);
+    let mut natives = Vec::new();
+    for native in resolve(&version.libraries, Platform::current()) {
+        if native.kind == NativeKind::Classifier {
+            let file_path = library_path.join(native.path()?);
+            natives.push(Job::new(DownloadTask::from_download(&native.download, file_path)));
         }
     }
+    failed.extend(scheduler.run(natives, &tracker, cancel)?);
 
     // 资源索引
-    let asset_index = &json["assetIndex"];
-    let binding = assets_path.clone().join("indexes").join(String::from(asset_index["id"].as_str().unwrap()) + ".json");
-    create_dir_all(binding.parent().unwrap()).expect("Could not create directory!");
-    let mut file = File::create(binding.as_path()).expect("Could not create Asset index file!");
-    let url = asset_index["url"].as_str().unwrap();
-    println!("Downloading Asset Index: {}", url);
-    let asset_index = client.get(url).send().expect("Errors while downloading Json!").text().expect("Errors while reading Json!");
-    file.write_all(asset_index.as_ref()).expect("Could not write to Asset index!");
-    let asset_index: Value = serde_json::from_str(&asset_index).unwrap();
-
-    // TODO: 下载源的支持
+    tracker.phase(InstallPhase::AssetIndex);
+    let asset_index = version.asset_index.as_ref().ok_or_else(|| missing("assetIndex"))?;
+    let binding = assets_path.join("indexes").join(format!("{}.json", asset_index.id));
+    let task = DownloadTask::new(asset_index.url.as_str(), binding)
+        .with_sha1(asset_index.sha1.as_deref())
+        .with_size(asset_index.size);
+    scheduler.run_one(task.clone(), &tracker, cancel)?;
+    let asset_index: AssetIndex = serde_json::from_str(&read_to_string(&task.path)?)?;
+
     // 资源
-    let mut keys: Vec<&String> = asset_index["objects"].as_object().unwrap().keys().collect();
-    keys.sort_by(|a, b| {
-        let temp_a = asset_index["objects"][a]["size"].as_u64().unwrap();
-        let temp_b = asset_index["objects"][b]["size"].as_u64().unwrap();
-        temp_b.cmp(&temp_a)
-    });
-    for i in keys {
-        let hash = asset_index["objects"][i]["hash"].as_str().unwrap();
-        let hash_short = &hash[0..2];
-        let temp = assets_path.clone().join(format!("objects/{}", hash_short));
-        let path = temp.as_path();
-        let temp = format!("{}{}/{}", ASSETS_URL, hash_short, hash);
-        let url = temp.as_str();
-        create_dir_all(path.clone()).expect("Could not create parent directories for Asset!");
-        let file = File::create(path.join(hash)).expect("Could not create Asset file!");
-        let url = String::from(url);
-        let file = file.try_clone().unwrap();
-        let c = client.clone();
-        pool.spawn(move || {
-            println!("Downloading Asset: {}", url);
-            download(&url, &file, &c);
+    tracker.phase(InstallPhase::Assets);
+    let mut objects: Vec<&AssetObject> = asset_index.objects.values().collect();
+    // 大文件先下载
+    objects.sort_by_key(|o| std::cmp::Reverse(o.size));
+    let mut assets = Vec::new();
+    for object in objects {
+        let relative = object_path(&object.hash)?;
+        let path = assets_path.join("objects").join(&relative);
+        let url = format!("{}/{}", RESOURCES, relative);
+        let task = DownloadTask::new(url, path)
+            .with_sha1(Some(object.hash.as_str()))
+            .with_size(Some(object.size));
+        assets.push(Job::new(task));
+    }
+    failed.extend(scheduler.run(assets, &tracker, cancel)?);
+    if !failed.is_empty() {
+        return Err(CoreError::DownloadFailed(failed.into_iter().map(|(task, e)| format!("{:?}: {}", task.path, e)).collect()));
+    }
+    tracker.phase(InstallPhase::Done);
+
+    Ok(tracker.snapshot())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::sync::Mutex;
+    use serde_json::json;
+    use sha1::{Digest, Sha1};
+    use crate::progress::ProgressEvent;
+    use crate::source::Mirror;
+    use crate::test_server::{Response, TestServer};
+
+    fn sha1_hex(data: &[u8]) -> String {
+        Sha1::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn file(base: &str, name: &str, data: &[u8]) -> serde_json::Value {
+        json!({"url": format!("{}/{}", base, name), "sha1": sha1_hex(data), "size": data.len()})
+    }
+
+    fn asset_index() -> String {
+        json!({"objects": {"minecraft/lang/en_us.json": {"hash": sha1_hex(b"asset"), "size": 5}}}).to_string()
+    }
+
+    // 一个依赖库下载失败的版本，资源从镜像下载
+    fn version_server() -> TestServer {
+        let asset = sha1_hex(b"asset");
+        let index = asset_index();
+        let base = Arc::new(Mutex::new(String::new()));
+        let b = base.clone();
+        let server = TestServer::start(move |req| {
+            let base = b.lock().unwrap().clone();
+            match req.path.as_str() {
+                "/version.json" => Response::json(200, &json!({
+                    "id": "test",
+                    "mainClass": "net.minecraft.client.main.Main",
+                    "downloads": {"client": file(&base, "client.jar", b"client")},
+                    "assetIndex": {"id": "test", "url": format!("{}/index.json", base), "sha1": sha1_hex(index.as_bytes()), "size": index.len(), "totalSize": 5},
+                    "libraries": [
+                        {"name": "a:good:1.0", "downloads": {"artifact": {"path": "a/good/1.0/good-1.0.jar", "url": format!("{}/good.jar", base), "sha1": sha1_hex(b"good"), "size": 4}}},
+                        {"name": "a:missing:1.0", "downloads": {"artifact": {"path": "a/missing/1.0/missing-1.0.jar", "url": format!("{}/missing.jar", base), "sha1": sha1_hex(b"missing"), "size": 7}}}
+                    ]
+                })),
+                "/client.jar" => Response::ok("client"),
+                "/good.jar" => Response::ok("good"),
+                "/index.json" => Response::ok(index.clone()),
+                path if path == format!("/resources/{}/{}", &asset[0..2], asset) => Response::ok("asset"),
+                _ => Response::not_found(),
+            }
         });
+        *base.lock().unwrap() = server.base_url.clone();
+        server
     }
 
-    pool.join(|| (), || ());
+    fn source(server: &TestServer) -> VersionSource {
+        VersionSource {
+            version_id: String::from("test"),
+            version_url: server.url("/version.json"),
+            version_type: String::from("release"),
+            release_time: String::new(),
+        }
+    }
+
+    fn game_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("akiraka-install-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn reports_progress_and_failures() {
+        let server = version_server();
+        let sources = DownloadSources::with_fallback(Mirror::new("Test").with_rule(RESOURCES, server.url("/resources")));
+        let dir = game_dir("progress");
+        let (sender, receiver) = channel();
+        let result = install(&source(&server), &sources, dir.clone(), 10_000, 2, Arc::new(sender), &CancelToken::new());
+        match result {
+            Err(CoreError::DownloadFailed(files)) => {
+                assert_eq!(files.len(), 1);
+                assert!(files[0].contains("missing-1.0.jar"), "{:?}", files);
+            }
+            result => panic!("Unexpected result {:?}", result.map(|_| ())),
+        }
 
-    Ok(())
-}
\ No newline at end of file
+        let events: Vec<ProgressEvent> = receiver.try_iter().collect();
+        let phases: Vec<InstallPhase> = events.iter().filter_map(|e| match e {
+            ProgressEvent::Phase(phase) => Some(*phase),
+            _ => None,
+        }).collect();
+        assert_eq!(phases, vec![
+            InstallPhase::Json,
+            InstallPhase::Client,
+            InstallPhase::Libraries,
+            InstallPhase::Natives,
+            InstallPhase::AssetIndex,
+            InstallPhase::Assets,
+        ]);
+        let failures: Vec<&String> = events.iter().filter_map(|e| match e {
+            ProgressEvent::Failed { url, .. } => Some(url),
+            _ => None,
+        }).collect();
+        assert_eq!(failures, vec![&server.url("/missing.jar")]);
+        let last = events.iter().rev().find_map(|e| match e {
+            ProgressEvent::Update(progress) => Some(progress),
+            _ => None,
+        }).unwrap();
+        // 主文件、两个依赖库、资源索引和一个资源
+        assert_eq!(last.total_files, 5);
+        assert_eq!(last.completed_files, 4);
+        assert_eq!(last.failed_files, 1);
+        assert_eq!(last.total_bytes, 6 + 4 + 7 + asset_index().len() as u64 + 5);
+        assert_eq!(last.completed_bytes, last.total_bytes - 7);
+        assert_eq!(last.phase, InstallPhase::Assets);
+        let asset = sha1_hex(b"asset");
+        assert!(Path::new(&dir).join("assets/objects").join(&asset[0..2]).join(&asset).is_file());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stops_when_cancelled() {
+        let server = version_server();
+        let dir = game_dir("cancel");
+        let cancel = CancelToken::new();
+        let token = cancel.clone();
+        // 开始下载依赖库时取消
+        let sink = move |event| if let ProgressEvent::Phase(InstallPhase::Libraries) = event {
+            token.cancel();
+        };
+        let result = install(&source(&server), &DownloadSources::official(), dir.clone(), 10_000, 2, Arc::new(sink), &cancel);
+        assert!(matches!(result, Err(CoreError::Cancelled)));
+        assert!(!server.requests().iter().any(|r| r.ends_with("/good.jar") || r.ends_with("/index.json")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn asset_object_paths() {
+        assert_eq!(object_path("bdf48ef6b5d0d23bbb02e17d04865216179f510a").unwrap(), "bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a");
+        // 长度不对或者不是十六进制时不能拼成路径，多字节字符也不能panic
+        for hash in ["", "bd", "../../../../etc/passwd", "é0f48ef6b5d0d23bbb02e17d04865216179f51a", "zzf48ef6b5d0d23bbb02e17d04865216179f510a"] {
+            assert!(object_path(hash).is_err(), "{}", hash);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::jvm::{GcPreset, JvmSettings, MemorySetting};
+use crate::launcher::LaunchOptions;
+use crate::{CoreError, Result};
+
+// 实例目录里的清单文件
+pub const MANIFEST_FILE: &str = "instance.json";
+
+// 实例使用的模组加载器
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoaderType {
+    Fabric,
+    Quilt,
+    Forge,
+    NeoForge,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceLoader {
+    #[serde(rename = "type")]
+    pub kind: LoaderType,
+    pub version: String,
+}
+
+// 窗口大小
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+// instance.json的内容，没有设置的项使用全局设置
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceManifest {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    // versions目录里的版本ID
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loader: Option<InstanceLoader>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub java: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemorySetting>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gc: Option<GcPreset>,
+    // 加在全局JVM参数后面，替换全局参数里同一个选项的参数
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub jvm_args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+}
+
+impl InstanceManifest {
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> InstanceManifest {
+        InstanceManifest {
+            name: name.into(),
+            icon: None,
+            version: version.into(),
+            loader: None,
+            java: None,
+            memory: None,
+            gc: None,
+            jvm_args: Vec::new(),
+            resolution: None,
+        }
+    }
+}
+
+// 一个实例：独立的游戏目录（存档、模组、设置），共用游戏根目录的依赖库、资源和版本
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instance {
+    // 目录名，重命名时不变
+    pub id: String,
+    pub dir: PathBuf,
+    pub manifest: InstanceManifest,
+}
+
+impl Instance {
+    fn load(dir: &Path) -> Result<Instance> {
+        let manifest = serde_json::from_str(&read_to_string(dir.join(MANIFEST_FILE))?)?;
+        let id = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(Instance { id, dir: dir.to_path_buf(), manifest })
+    }
+
+    fn save(&self) -> Result<()> {
+        create_dir_all(&self.dir)?;
+        write(self.dir.join(MANIFEST_FILE), serde_json::to_string_pretty(&self.manifest)?)?;
+        Ok(())
+    }
+
+    // 使用实例目录作为${game_directory}的启动选项，global为全局的JVM设置
+    pub fn launch_options(&self, global: &JvmSettings) -> LaunchOptions {
+        LaunchOptions {
+            game_dir: Some(self.dir.clone()),
+            width: self.manifest.resolution.map(|r| r.width),
+            height: self.manifest.resolution.map(|r| r.height),
+            jvm: global.with_overrides(self.manifest.memory, self.manifest.gc, &self.manifest.jvm_args),
+            java: self.manifest.java.clone(),
+            ..LaunchOptions::default()
+        }
+    }
+}
+
+// 名字转换为目录名，去掉文件名里不能用的字符
+fn dir_name(name: &str) -> String {
+    let name: String = name.trim().chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    let name = name.trim_end_matches('.');
+    if name.is_empty() {
+        String::from("instance")
+    } else {
+        String::from(name)
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    create_dir_all(to)?;
+    for entry in read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+// 游戏根目录下instances里的所有实例
+pub struct InstanceStore {
+    root: PathBuf,
+}
+
+impl InstanceStore {
+    // root为游戏根目录，versions、libraries和assets在所有实例间共用
+    pub fn new(root: impl Into<PathBuf>) -> InstanceStore {
+        InstanceStore { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn instances_dir(&self) -> PathBuf {
+        self.root.join("instances")
+    }
+
+    // 按名字排序，没有清单或清单损坏的目录直接跳过
+    pub fn list(&self) -> Result<Vec<Instance>> {
+        let dir = self.instances_dir();
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut instances = Vec::new();
+        for entry in read_dir(&dir)? {
+            let path = entry?.path();
+            if let Ok(instance) = Instance::load(&path) {
+                instances.push(instance);
+            }
+        }
+        instances.sort_by_key(|i| i.manifest.name.to_lowercase());
+        Ok(instances)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Instance> {
+        let dir = self.instances_dir().join(id);
+        // ID只能是instances下的一层目录
+        if id.is_empty() || id == ".." || id.contains(['/', '\\']) || !dir.join(MANIFEST_FILE).is_file() {
+            return Err(CoreError::MissingInstance(String::from(id)));
+        }
+        Instance::load(&dir)
+    }
+
+    // 名字对应的目录已经存在时加上序号
+    fn unused_id(&self, name: &str) -> String {
+        let base = dir_name(name);
+        let mut id = base.clone();
+        let mut index = 2;
+        while self.instances_dir().join(&id).exists() {
+            id = format!("{}-{}", base, index);
+            index += 1;
+        }
+        id
+    }
+
+    fn check_name(name: &str) -> Result<()> {
+        if name.trim().is_empty() {
+            return Err(CoreError::InvalidInstanceName(String::from(name)));
+        }
+        Ok(())
+    }
+
+    pub fn create(&self, manifest: InstanceManifest) -> Result<Instance> {
+        InstanceStore::check_name(&manifest.name)?;
+        let id = self.unused_id(&manifest.name);
+        let instance = Instance { dir: self.instances_dir().join(&id), id, manifest };
+        instance.save()?;
+        Ok(instance)
+    }
+
+    // 保存修改过的清单
+    pub fn save(&self, instance: &Instance) -> Result<()> {
+        self.get(&instance.id)?;
+        instance.save()
+    }
+
+    // 只修改显示的名字，目录名不变
+    pub fn rename(&self, id: &str, name: &str) -> Result<Instance> {
+        InstanceStore::check_name(name)?;
+        let mut instance = self.get(id)?;
+        instance.manifest.name = String::from(name);
+        instance.save()?;
+        Ok(instance)
+    }
+
+    // 复制整个实例目录，包括存档和模组
+    pub fn clone(&self, id: &str, name: &str) -> Result<Instance> {
+        InstanceStore::check_name(name)?;
+        let source = self.get(id)?;
+        let new_id = self.unused_id(name);
+        let dir = self.instances_dir().join(&new_id);
+        copy_dir(&source.dir, &dir)?;
+        let mut manifest = source.manifest;
+        manifest.name = String::from(name);
+        let instance = Instance { id: new_id, dir, manifest };
+        instance.save()?;
+        Ok(instance)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<()> {
+        let instance = self.get(id)?;
+        remove_dir_all(instance.dir)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(name: &str) -> InstanceStore {
+        let root = std::env::temp_dir().join(format!("akiraka-instances-{}-{}", name, std::process::id()));
+        let _ = remove_dir_all(&root);
+        InstanceStore::new(root)
+    }
+
+    #[test]
+    fn manages_instances() {
+        let store = store("manage");
+        assert!(store.list().unwrap().is_empty());
+
+        let mut manifest = InstanceManifest::new("Survival: 1.20", "fabric-loader-0.14.21-1.20.1");
+        manifest.loader = Some(InstanceLoader { kind: LoaderType::Fabric, version: String::from("0.14.21") });
+        manifest.resolution = Some(Resolution { width: 1280, height: 720 });
+        manifest.memory = Some(MemorySetting::Fixed { max: 6144, min: None });
+        manifest.java = Some(PathBuf::from("/jvm/17/bin/java"));
+        let survival = store.create(manifest).unwrap();
+        assert_eq!(survival.id, "Survival_ 1.20");
+        write(survival.dir.join("options.txt"), "lang:zh_cn").unwrap();
+        create_dir_all(survival.dir.join("saves/World")).unwrap();
+        write(survival.dir.join("saves/World/level.dat"), "level").unwrap();
+
+        // 同名的实例使用不同的目录
+        let other = store.create(InstanceManifest::new("Survival: 1.20", "1.20.1")).unwrap();
+        assert_eq!(other.id, "Survival_ 1.20-2");
+        assert!(matches!(store.create(InstanceManifest::new("  ", "1.20.1")), Err(CoreError::InvalidInstanceName(_))));
+
+        let renamed = store.rename(&other.id, "Creative").unwrap();
+        assert_eq!(renamed.id, other.id);
+        let copied = store.clone(&survival.id, "Backup").unwrap();
+        assert_eq!(read_to_string(copied.dir.join("saves/World/level.dat")).unwrap(), "level");
+        assert_eq!(copied.manifest.loader, survival.manifest.loader);
+
+        let names: Vec<String> = store.list().unwrap().into_iter().map(|i| i.manifest.name).collect();
+        assert_eq!(names, vec!["Backup", "Creative", "Survival: 1.20"]);
+
+        let global = JvmSettings { gc: GcPreset::G1, ..JvmSettings::default() };
+        let options = store.get(&survival.id).unwrap().launch_options(&global);
+        assert_eq!(options.jvm.memory, MemorySetting::Fixed { max: 6144, min: None });
+        assert_eq!(options.jvm.gc, GcPreset::G1);
+        assert_eq!(options.java.as_deref(), Some(Path::new("/jvm/17/bin/java")));
+        assert_eq!(options.game_dir.as_deref(), Some(survival.dir.as_path()));
+        assert_eq!((options.width, options.height), (Some(1280), Some(720)));
+
+        store.delete(&survival.id).unwrap();
+        assert!(!survival.dir.exists());
+        assert!(matches!(store.get(&survival.id), Err(CoreError::MissingInstance(_))));
+        assert_eq!(store.list().unwrap().len(), 2);
+        remove_dir_all(store.root()).unwrap();
+    }
+}
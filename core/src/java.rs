@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::env;
+use std::env::consts::OS;
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use crate::model::Version;
+use crate::{CoreError, Result};
+
+// 没有javaVersion的老版本使用爪哇8
+const DEFAULT_MAJOR_VERSION: u32 = 8;
+
+// 爪哇可执行文件的名字
+#[cfg(target_os = "windows")]
+pub const JAVA_EXECUTABLE: &str = "java.exe";
+#[cfg(not(target_os = "windows"))]
+pub const JAVA_EXECUTABLE: &str = "java";
+
+// 搜索启动器运行时目录时的最大深度（runtime/<组件>/<平台>/<组件>/bin/java）
+const RUNTIME_DEPTH: usize = 4;
+
+// 爪哇的版本信息
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JavaInfo {
+    pub home: PathBuf,
+    pub major_version: u32,
+    pub version: String,
+    pub vendor: String,
+    // 与os.arch相同的写法（amd64、x86、aarch64……）
+    pub arch: String,
+    pub is_64bit: bool,
+}
+
+// 找到的爪哇
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JavaInstallation {
+    pub path: PathBuf,
+    pub info: JavaInfo,
+}
+
+impl JavaInstallation {
+    // 检查一个爪哇，不可用时返回None
+    pub fn probe(path: &Path) -> Option<JavaInstallation> {
+        Some(JavaInstallation {
+            path: path.to_path_buf(),
+            info: probe_cached(path).ok()?,
+        })
+    }
+}
+
+// 从版本号获取主版本号："1.8.0_381" -> 8，"17.0.2" -> 17
+pub fn major_version(version: &str) -> Option<u32> {
+    let mut parts = version.trim().trim_matches('"').split(|c: char| !c.is_ascii_digit());
+    let first: u32 = parts.next()?.parse().ok()?;
+    if first == 1 {
+        parts.next()?.parse().ok()
+    } else {
+        Some(first)
+    }
+}
+
+// 统一为os.arch的写法
+pub(crate) fn normalize_arch(arch: &str) -> String {
+    match arch.to_lowercase().as_str() {
+        "x86_64" | "x64" => String::from("amd64"),
+        "i386" | "i486" | "i586" | "i686" => String::from("x86"),
+        "arm64" => String::from("aarch64"),
+        other => String::from(other),
+    }
+}
+
+fn is_64bit_arch(arch: &str) -> bool {
+    matches!(arch, "amd64" | "aarch64" | "ppc64" | "ppc64le" | "s390x" | "riscv64" | "loongarch64" | "sparcv9" | "mips64el")
+}
+
+// 解析爪哇目录下的release文件
+fn parse_release(text: &str, home: &Path) -> Option<JavaInfo> {
+    let values: HashMap<&str, &str> = text.lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim().trim_matches('"')))
+        .collect();
+    let version = *values.get("JAVA_VERSION")?;
+    let arch = normalize_arch(values.get("OS_ARCH")?);
+    Some(JavaInfo {
+        home: home.to_path_buf(),
+        major_version: major_version(version)?,
+        version: String::from(version),
+        vendor: String::from(values.get("IMPLEMENTOR").copied().unwrap_or_default()),
+        is_64bit: is_64bit_arch(&arch),
+        arch,
+    })
+}
+
+// 解析 java -XshowSettings:properties -version 的输出
+fn parse_properties(text: &str) -> Option<JavaInfo> {
+    let values: HashMap<&str, &str> = text.lines()
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .collect();
+    let version = *values.get("java.version")?;
+    let arch = normalize_arch(values.get("os.arch")?);
+    let is_64bit = match values.get("sun.arch.data.model") {
+        Some(model) => *model == "64",
+        None => is_64bit_arch(&arch),
+    };
+    Some(JavaInfo {
+        home: PathBuf::from(values.get("java.home")?),
+        major_version: major_version(version)?,
+        version: String::from(version),
+        vendor: String::from(values.get("java.vendor").copied().unwrap_or_default()),
+        arch,
+        is_64bit,
+    })
+}
+
+// 检查爪哇：先读release文件，读不到时运行爪哇
+pub fn probe(java: &Path) -> Result<JavaInfo> {
+    let java = java.canonicalize()
+        .map_err(|e| CoreError::Java(format!("Could not find {:?}: {}", java, e)))?;
+
+    // bin/java -> 爪哇目录，JDK 8的jre/bin/java还要再往上一层
+    if let Some(home) = java.parent().and_then(Path::parent) {
+        for dir in [Some(home), home.parent()].into_iter().flatten() {
+            if let Ok(text) = read_to_string(dir.join("release")) {
+                if let Some(info) = parse_release(&text, home) {
+                    return Ok(info);
+                }
+            }
+        }
+    }
+
+    let output = Command::new(&java)
+        .arg("-XshowSettings:properties")
+        .arg("-version")
+        .output()
+        .map_err(|e| CoreError::Java(format!("Could not execute {:?}: {}", java, e)))?;
+    // 设置打印在stderr里
+    let text = String::from_utf8_lossy(&output.stderr);
+    parse_properties(&text)
+        .ok_or_else(|| CoreError::Java(format!("Could not read the version of {:?}!", java)))
+}
+
+fn cache() -> &'static Mutex<HashMap<PathBuf, (SystemTime, JavaInfo)>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, (SystemTime, JavaInfo)>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// 带缓存的检查，以路径和修改时间为键
+pub fn probe_cached(java: &Path) -> Result<JavaInfo> {
+    let path = java.canonicalize()
+        .map_err(|e| CoreError::Java(format!("Could not find {:?}: {}", java, e)))?;
+    let modified = path.metadata()?.modified()?;
+    if let Some((time, info)) = cache().lock().unwrap_or_else(|e| e.into_inner()).get(&path) {
+        if *time == modified {
+            return Ok(info.clone());
+        }
+    }
+    let info = probe(&path)?;
+    cache().lock().unwrap_or_else(|e| e.into_inner()).insert(path, (modified, info.clone()));
+    Ok(info)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os(if OS == "windows" { "USERPROFILE" } else { "HOME" }).map(PathBuf::from)
+}
+
+// 爪哇目录下的可执行文件
+pub(crate) fn executable_in(home: &Path) -> Option<PathBuf> {
+    [home.join("bin").join(JAVA_EXECUTABLE), home.join("Contents/Home/bin").join(JAVA_EXECUTABLE)]
+        .into_iter()
+        .find(|p| p.is_file())
+}
+
+// 目录下每个子目录里的爪哇
+fn scan_children(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if let Some(java) = executable_in(&entry.path()) {
+            out.push(java);
+        }
+    }
+}
+
+// 递归搜索启动器的运行时目录
+fn scan_runtime(dir: &Path, depth: usize, out: &mut Vec<PathBuf>) {
+    if let Some(java) = executable_in(dir) {
+        out.push(java);
+        return;
+    }
+    if depth == 0 {
+        return;
+    }
+    let Ok(entries) = read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.path().is_dir() {
+            scan_runtime(&entry.path(), depth - 1, out);
+        }
+    }
+}
+
+// 所有可能的爪哇路径，未检查是否可用
+pub fn candidates(runtime_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+
+    // JAVA_HOME
+    if let Some(home) = env::var_os("JAVA_HOME") {
+        if let Some(java) = executable_in(Path::new(&home)) {
+            out.push(java);
+        }
+    }
+
+    // PATH
+    if let Some(paths) = env::var_os("PATH") {
+        for dir in env::split_paths(&paths) {
+            let java = dir.join(JAVA_EXECUTABLE);
+            if java.is_file() {
+                out.push(java);
+            }
+        }
+    }
+
+    // 系统的安装目录
+    let mut roots: Vec<PathBuf> = match OS {
+        "windows" => ["ProgramFiles", "ProgramFiles(x86)"]
+            .iter()
+            .filter_map(env::var_os)
+            .flat_map(|p| {
+                let p = PathBuf::from(p);
+                ["Java", "Eclipse Adoptium", "Zulu", "Microsoft", "BellSoft", "Amazon Corretto"].map(|v| p.join(v))
+            })
+            .collect(),
+        "macos" => vec![PathBuf::from("/Library/Java/JavaVirtualMachines")],
+        _ => vec![PathBuf::from("/usr/lib/jvm"), PathBuf::from("/usr/java"), PathBuf::from("/opt")],
+    };
+
+    // SDKMAN和IntelliJ下载的JDK
+    if let Some(home) = home_dir() {
+        roots.push(home.join(".sdkman/candidates/java"));
+        roots.push(home.join(".jdks"));
+        if OS == "macos" {
+            roots.push(home.join("Library/Java/JavaVirtualMachines"));
+        }
+    }
+    for root in roots {
+        scan_children(&root, &mut out);
+    }
+
+    // 启动器自己的运行时
+    if let Some(runtime_dir) = runtime_dir {
+        scan_runtime(runtime_dir, RUNTIME_DEPTH, &mut out);
+    }
+
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for path in out {
+        if !unique.contains(&path) {
+            unique.push(path);
+        }
+    }
+    unique
+}
+
+// 搜索并检查所有爪哇，按爪哇目录去重
+pub fn discover(runtime_dir: Option<&Path>) -> Vec<JavaInstallation> {
+    let mut found: Vec<JavaInstallation> = Vec::new();
+    for path in candidates(runtime_dir) {
+        let Some(java) = JavaInstallation::probe(&path) else {
+            continue
+        };
+        if !found.iter().any(|j| j.info.home == java.info.home) {
+            found.push(java);
+        }
+    }
+    found.sort_by_key(|j| std::cmp::Reverse(j.info.major_version));
+    found
+}
+
+// 版本Json要求的爪哇主版本号
+pub fn required_major_version(version: &Version) -> u32 {
+    version.java_version.as_ref().and_then(|j| j.major_version).unwrap_or(DEFAULT_MAJOR_VERSION)
+}
+
+// 要求的版本是否可以用这个主版本号运行：需要爪哇8及以前的版本（LaunchWrapper等）在新版本上会崩溃
+pub fn is_compatible(required: u32, major: u32) -> bool {
+    if required <= 8 {
+        major == required
+    } else {
+        major >= required
+    }
+}
+
+// 选择最合适的爪哇：主版本号相同的优先，其次是64位，再其次是版本最接近的
+pub fn select(installations: &[JavaInstallation], required: u32) -> Result<&JavaInstallation> {
+    installations.iter()
+        .filter(|j| is_compatible(required, j.info.major_version))
+        .min_by_key(|j| (j.info.major_version != required, !j.info.is_64bit, j.info.major_version))
+        .ok_or(CoreError::NoCompatibleJava(required))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_java_in_launcher_runtime() {
+        let runtime = env::temp_dir().join(format!("akiraka-java-runtime-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&runtime);
+        let bin = runtime.join("java-runtime-gamma").join(OS).join("java-runtime-gamma").join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join(JAVA_EXECUTABLE), b"").unwrap();
+
+        let found = candidates(Some(&runtime));
+        assert!(found.contains(&bin.join(JAVA_EXECUTABLE)));
+        assert_eq!(found.iter().filter(|p| p.starts_with(&runtime)).count(), 1);
+    }
+
+    fn installation(major: u32, is_64bit: bool) -> JavaInstallation {
+        JavaInstallation {
+            path: PathBuf::from(format!("/jvm/{}-{}/bin/java", major, is_64bit)),
+            info: JavaInfo {
+                home: PathBuf::from(format!("/jvm/{}-{}", major, is_64bit)),
+                major_version: major,
+                version: major.to_string(),
+                vendor: String::new(),
+                arch: String::from(if is_64bit { "amd64" } else { "x86" }),
+                is_64bit,
+            },
+        }
+    }
+
+    #[test]
+    fn selects_best_java() {
+        let list = vec![installation(21, true), installation(8, false), installation(8, true), installation(17, true)];
+        assert_eq!(select(&list, 8).unwrap(), &list[2]);
+        assert_eq!(select(&list, 17).unwrap(), &list[3]);
+        assert_eq!(select(&list, 16).unwrap(), &list[3]);
+        assert_eq!(select(&list, 21).unwrap(), &list[0]);
+        assert!(matches!(select(&list, 25), Err(CoreError::NoCompatibleJava(25))));
+        assert!(matches!(select(&[], 8), Err(CoreError::NoCompatibleJava(8))));
+        // 需要爪哇8时不使用新版本
+        let modern = vec![installation(17, true), installation(21, true)];
+        assert!(matches!(select(&modern, 8), Err(CoreError::NoCompatibleJava(8))));
+    }
+
+    #[test]
+    fn reads_required_version() {
+        let version = |json| serde_json::from_value::<Version>(json).unwrap();
+        assert_eq!(required_major_version(&version(serde_json::json!({"id": "1.20.1", "javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17}}))), 17);
+        assert_eq!(required_major_version(&version(serde_json::json!({"id": "1.12.2"}))), 8);
+    }
+
+    #[test]
+    fn parses_major_versions() {
+        assert_eq!(major_version("1.8.0_381"), Some(8));
+        assert_eq!(major_version("17.0.2"), Some(17));
+        assert_eq!(major_version("21"), Some(21));
+        assert_eq!(major_version("\"16.0.1\""), Some(16));
+        assert_eq!(major_version("abc"), None);
+    }
+
+    #[test]
+    fn parses_release_file() {
+        let release = "IMPLEMENTOR=\"Eclipse Adoptium\"\nJAVA_VERSION=\"1.8.0_392\"\nOS_ARCH=\"x86_64\"\nOS_NAME=\"Linux\"\n";
+        let info = parse_release(release, Path::new("/opt/jdk8")).unwrap();
+        assert_eq!(info.major_version, 8);
+        assert_eq!(info.version, "1.8.0_392");
+        assert_eq!(info.vendor, "Eclipse Adoptium");
+        assert_eq!(info.arch, "amd64");
+        assert!(info.is_64bit);
+        assert!(parse_release("IMPLEMENTOR=\"Nobody\"", Path::new("/")).is_none());
+    }
+
+    #[test]
+    fn parses_show_settings_output() {
+        let output = "Property settings:\n    java.home = /usr/lib/jvm/zulu-17\n    java.library.path = /usr/lib\n        /lib\n    java.vendor = Azul Systems, Inc.\n    java.version = 17.0.9\n    os.arch = x86\n    sun.arch.data.model = 32\n\nopenjdk version \"17.0.9\"\n";
+        let info = parse_properties(output).unwrap();
+        assert_eq!(info.home, PathBuf::from("/usr/lib/jvm/zulu-17"));
+        assert_eq!(info.major_version, 17);
+        assert_eq!(info.vendor, "Azul Systems, Inc.");
+        assert_eq!(info.arch, "x86");
+        assert!(!info.is_64bit);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn falls_back_to_show_settings_and_caches() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = env::temp_dir().join(format!("akiraka-java-probe-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(home.join("bin")).unwrap();
+        let counter = home.join("count");
+        let java = home.join("bin/java");
+        std::fs::write(&java, format!(
+            "#!/bin/sh\necho x >> {:?}\necho '    java.home = /fake' >&2\necho '    java.version = 21.0.1' >&2\necho '    java.vendor = Fake' >&2\necho '    os.arch = aarch64' >&2\n",
+            counter
+        )).unwrap();
+        std::fs::set_permissions(&java, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let info = probe_cached(&java).unwrap();
+        assert_eq!(info.major_version, 21);
+        assert_eq!(info.arch, "aarch64");
+        assert!(info.is_64bit);
+        assert_eq!(probe_cached(&java).unwrap(), info);
+        assert_eq!(read_to_string(&counter).unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,340 @@
+use std::fs::read_dir;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use sysinfo::{System, SystemExt};
+use crate::{CoreError, Result};
+
+const MB: u64 = 1024 * 1024;
+// 自动分配时的范围，单位MB
+const MIN_HEAP: u32 = 1024;
+const MAX_HEAP: u32 = 16384;
+
+// 最大内存的设置方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum MemorySetting {
+    // 根据系统内存和模组大小自动分配
+    #[default]
+    Auto,
+    // 固定大小，单位MB
+    Fixed {
+        max: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<u32>,
+    },
+}
+
+// 垃圾回收器预设
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GcPreset {
+    // 不指定，使用爪哇的默认值
+    #[default]
+    Default,
+    // 官方启动器使用的G1参数
+    G1,
+    Zgc,
+    // Aikar's flags，同样基于G1
+    Aikar,
+}
+
+// 选择垃圾回收器的参数
+const GC_FLAGS: [&str; 6] = [
+    "-XX:+UseG1GC",
+    "-XX:+UseZGC",
+    "-XX:+UseParallelGC",
+    "-XX:+UseSerialGC",
+    "-XX:+UseShenandoahGC",
+    "-XX:+UseConcMarkSweepGC",
+];
+
+impl GcPreset {
+    pub fn flags(&self, java_major: u32) -> Result<Vec<&'static str>> {
+        Ok(match self {
+            GcPreset::Default => Vec::new(),
+            GcPreset::G1 => vec![
+                "-XX:+UseG1GC",
+                "-XX:+UnlockExperimentalVMOptions",
+                "-XX:G1NewSizePercent=20",
+                "-XX:G1ReservePercent=20",
+                "-XX:MaxGCPauseMillis=50",
+                "-XX:G1HeapRegionSize=32M",
+            ],
+            GcPreset::Zgc => match java_major {
+                0..=10 => return Err(CoreError::JvmArguments(format!("ZGC requires Java 11 or newer, got Java {}!", java_major))),
+                // 15以前是实验功能
+                11..=14 => vec!["-XX:+UnlockExperimentalVMOptions", "-XX:+UseZGC"],
+                _ => vec!["-XX:+UseZGC"],
+            },
+            GcPreset::Aikar => vec![
+                "-XX:+UseG1GC",
+                "-XX:+ParallelRefProcEnabled",
+                "-XX:MaxGCPauseMillis=200",
+                "-XX:+UnlockExperimentalVMOptions",
+                "-XX:+DisableExplicitGC",
+                "-XX:+AlwaysPreTouch",
+                "-XX:G1NewSizePercent=30",
+                "-XX:G1MaxNewSizePercent=40",
+                "-XX:G1HeapRegionSize=8M",
+                "-XX:G1ReservePercent=20",
+                "-XX:G1HeapWastePercent=5",
+                "-XX:G1MixedGCCountTarget=4",
+                "-XX:InitiatingHeapOccupancyPercent=15",
+                "-XX:G1MixedGCLiveThresholdPercent=90",
+                "-XX:G1RSetUpdatingPauseTimePercent=5",
+                "-XX:SurvivorRatio=32",
+                "-XX:+PerfDisableSharedMem",
+                "-XX:MaxTenuringThreshold=1",
+            ],
+        })
+    }
+}
+
+// 目录里所有文件的大小，单位MB
+fn dir_size(dir: &Path) -> u64 {
+    fn bytes(dir: &Path) -> u64 {
+        let Ok(entries) = read_dir(dir) else {
+            return 0;
+        };
+        entries.flatten()
+            .map(|e| match e.file_type() {
+                Ok(t) if t.is_dir() => bytes(&e.path()),
+                _ => e.metadata().map(|m| m.len()).unwrap_or(0),
+            })
+            .sum()
+    }
+    bytes(dir) / MB
+}
+
+// 自动分配的最大内存：原版2G，每MB模组再加10MB，不超过空闲内存也不占满整个系统
+pub fn auto_heap(total_mb: u64, available_mb: u64, mods_mb: u64) -> u32 {
+    let wanted = 2048 + mods_mb * 10;
+    // 至少留1G给系统，内存紧张时也可以使用总内存的1/4
+    let limit = available_mb.saturating_sub(1024).max(total_mb / 4).min(total_mb * 3 / 4);
+    let heap = wanted.min(limit).min(MAX_HEAP as u64) as u32;
+    // 按256MB取整，内存极小时也不超过物理内存
+    (heap / 256 * 256).max(MIN_HEAP).min(total_mb.min(u32::MAX as u64) as u32)
+}
+
+// 读取系统内存并计算最大内存
+fn system_heap(game_dir: &Path) -> u32 {
+    let mut system = System::new();
+    system.refresh_memory();
+    auto_heap(system.total_memory() / MB, system.available_memory() / MB, dir_size(&game_dir.join("mods")))
+}
+
+// 内存大小参数的值，例如4G、512m，单位MB
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len()));
+    let number: u64 = number.parse().ok()?;
+    match unit.to_ascii_lowercase().as_str() {
+        "" => Some(number / MB),
+        "k" => Some(number / 1024),
+        "m" => Some(number),
+        "g" => Some(number * 1024),
+        _ => None,
+    }
+}
+
+// 启动时的JVM设置
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JvmSettings {
+    pub memory: MemorySetting,
+    pub gc: GcPreset,
+    // 自定义参数，放在最后
+    pub args: Vec<String>,
+}
+
+impl JvmSettings {
+    // 检查自定义参数之间以及和内存、回收器设置之间的冲突
+    pub fn validate(&self, java_major: u32) -> Result<()> {
+        let mut gcs: Vec<&str> = self.gc.flags(java_major)?.into_iter().filter(|f| GC_FLAGS.contains(f)).collect();
+        let mut max = None;
+        let mut min = None;
+        for arg in &self.args {
+            if GC_FLAGS.contains(&arg.as_str()) {
+                gcs.push(arg);
+            } else if let Some(value) = arg.strip_prefix("-Xmx") {
+                if max.replace(value).is_some_and(|m| m != value) {
+                    return Err(CoreError::JvmArguments(String::from("-Xmx is set more than once!")));
+                }
+            } else if let Some(value) = arg.strip_prefix("-Xms") {
+                if min.replace(value).is_some_and(|m| m != value) {
+                    return Err(CoreError::JvmArguments(String::from("-Xms is set more than once!")));
+                }
+            }
+        }
+        gcs.sort_unstable();
+        gcs.dedup();
+        if gcs.len() > 1 {
+            return Err(CoreError::JvmArguments(format!("Conflicting garbage collectors: {}", gcs.join(", "))));
+        }
+        if matches!(self.memory, MemorySetting::Fixed { .. }) && (max.is_some() || min.is_some()) {
+            return Err(CoreError::JvmArguments(String::from("-Xmx/-Xms conflict with the memory setting!")));
+        }
+        let (max, min) = match self.memory {
+            MemorySetting::Fixed { max, min } => (Some(max as u64), min.map(|m| m as u64)),
+            MemorySetting::Auto => (max.and_then(parse_size), min.and_then(parse_size)),
+        };
+        if let (Some(max), Some(min)) = (max, min) {
+            if min > max {
+                return Err(CoreError::JvmArguments(format!("Initial heap {}M is larger than maximum heap {}M!", min, max)));
+            }
+        }
+        Ok(())
+    }
+
+    // 内存、回收器和自定义参数，game_dir用来计算模组大小
+    pub fn arguments(&self, game_dir: &Path, java_major: u32) -> Result<Vec<String>> {
+        self.validate(java_major)?;
+        let mut arguments = Vec::new();
+        match self.memory {
+            // 自定义参数里指定了-Xmx时不再自动分配
+            MemorySetting::Auto if self.args.iter().any(|a| a.starts_with("-Xmx")) => {}
+            MemorySetting::Auto => {
+                // 自动分配的内存不能比自定义参数里的-Xms小，否则虚拟机无法启动
+                let min = self.args.iter().filter_map(|a| a.strip_prefix("-Xms")).find_map(parse_size).unwrap_or(0);
+                arguments.push(format!("-Xmx{}M", (system_heap(game_dir) as u64).max(min)));
+            }
+            MemorySetting::Fixed { max, min } => {
+                if let Some(min) = min {
+                    arguments.push(format!("-Xms{}M", min));
+                }
+                arguments.push(format!("-Xmx{}M", max));
+            }
+        }
+        arguments.extend(self.gc.flags(java_major)?.into_iter().map(String::from));
+        arguments.extend(self.args.iter().cloned());
+        Ok(arguments)
+    }
+
+    // 实例的设置覆盖全局设置，实例的自定义参数替换全局参数里同一个选项的参数
+    pub fn with_overrides(&self, memory: Option<MemorySetting>, gc: Option<GcPreset>, args: &[String]) -> JvmSettings {
+        let keys: Vec<&str> = args.iter().map(|a| arg_key(a)).collect();
+        let sets_memory = keys.contains(&"-Xmx") || keys.contains(&"-Xms");
+        let sets_gc = keys.contains(&"gc");
+        // 实例设置了内存或回收器时，全局参数里对应的参数也不再使用
+        let overridden = |key: &str| match key {
+            "-Xmx" | "-Xms" => memory.is_some() || sets_memory,
+            "gc" => gc.is_some() || sets_gc,
+            _ => keys.contains(&key),
+        };
+        JvmSettings {
+            // 实例参数里的-Xmx、回收器优先于全局的内存和回收器设置
+            memory: memory.unwrap_or(if sets_memory { MemorySetting::Auto } else { self.memory }),
+            gc: gc.unwrap_or(if sets_gc { GcPreset::Default } else { self.gc }),
+            args: self.args.iter()
+                .filter(|a| !overridden(arg_key(a)))
+                .chain(args)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+// 参数对应的选项，同一个选项的参数互相覆盖
+fn arg_key(arg: &str) -> &str {
+    if GC_FLAGS.contains(&arg) {
+        return "gc";
+    }
+    for prefix in ["-Xmx", "-Xms", "-Xss", "-Xmn"] {
+        if arg.starts_with(prefix) {
+            return prefix;
+        }
+    }
+    // -XX:+Foo、-XX:-Foo和-XX:Foo=1都是Foo
+    if let Some(option) = arg.strip_prefix("-XX:") {
+        let option = option.trim_start_matches(['+', '-']);
+        return option.split('=').next().unwrap_or(option);
+    }
+    // -Dname=value
+    if arg.starts_with("-D") {
+        return arg.split('=').next().unwrap_or(arg);
+    }
+    arg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| String::from(*a)).collect()
+    }
+
+    #[test]
+    fn sizes_heap_automatically() {
+        // 16G内存，空闲10G
+        assert_eq!(auto_heap(16384, 10240, 0), 2048);
+        assert_eq!(auto_heap(16384, 10240, 400), 5888);
+        // 不超过空闲内存减1G
+        assert_eq!(auto_heap(16384, 5120, 800), 4096);
+        // 空闲内存很少时至少用总内存的1/4
+        assert_eq!(auto_heap(8192, 1024, 0), 2048);
+        // 总内存很小时不低于1G
+        assert_eq!(auto_heap(2048, 512, 0), 1024);
+        assert_eq!(auto_heap(131072, 120000, 4000), 16384);
+        // 不超过物理内存
+        assert_eq!(auto_heap(768, 256, 0), 768);
+        assert_eq!((parse_size("4G"), parse_size("512m"), parse_size("2x")), (Some(4096), Some(512), None));
+    }
+
+    #[test]
+    fn builds_arguments() {
+        let dir = Path::new("missing");
+        let fixed = JvmSettings {
+            memory: MemorySetting::Fixed { max: 4096, min: Some(1024) },
+            gc: GcPreset::Zgc,
+            args: args(&["-Dfile.encoding=UTF-8"]),
+        };
+        assert_eq!(fixed.arguments(dir, 17).unwrap(), args(&["-Xms1024M", "-Xmx4096M", "-XX:+UseZGC", "-Dfile.encoding=UTF-8"]));
+        assert_eq!(fixed.arguments(dir, 11).unwrap()[2], "-XX:+UnlockExperimentalVMOptions");
+        assert!(fixed.arguments(dir, 8).is_err());
+
+        // 自定义参数里的-Xmx优先于自动分配
+        let custom = JvmSettings { args: args(&["-Xmx3G"]), ..JvmSettings::default() };
+        assert_eq!(custom.arguments(dir, 17).unwrap(), args(&["-Xmx3G"]));
+        let auto = JvmSettings::default().arguments(dir, 17).unwrap();
+        assert!(auto.len() == 1 && auto[0].starts_with("-Xmx"));
+        let initial = JvmSettings { args: args(&["-Xms64G"]), ..JvmSettings::default() };
+        assert_eq!(initial.arguments(dir, 17).unwrap(), args(&["-Xmx65536M", "-Xms64G"]));
+
+        let instance = fixed.with_overrides(Some(MemorySetting::Auto), None, &args(&["-Dsodium.checks=false"]));
+        assert_eq!(instance.gc, GcPreset::Zgc);
+        assert_eq!(instance.args, args(&["-Dfile.encoding=UTF-8", "-Dsodium.checks=false"]));
+
+        // 实例的参数替换全局的同名参数，不算重复设置
+        let global = JvmSettings {
+            memory: MemorySetting::Fixed { max: 4096, min: None },
+            gc: GcPreset::G1,
+            args: args(&["-Dfile.encoding=UTF-8", "-XX:+UseStringDeduplication", "-XX:MaxGCPauseMillis=50"]),
+        };
+        let instance = global.with_overrides(None, None, &args(&["-Xmx8G", "-XX:+UseZGC", "-Dfile.encoding=GBK", "-XX:-UseStringDeduplication"]));
+        assert_eq!((instance.memory, instance.gc), (MemorySetting::Auto, GcPreset::Default));
+        assert_eq!(instance.args, args(&["-XX:MaxGCPauseMillis=50", "-Xmx8G", "-XX:+UseZGC", "-Dfile.encoding=GBK", "-XX:-UseStringDeduplication"]));
+        assert!(instance.validate(17).is_ok());
+        let global = JvmSettings { args: args(&["-Xmx2G", "-XX:+UseParallelGC"]), ..JvmSettings::default() };
+        let instance = global.with_overrides(Some(MemorySetting::Fixed { max: 6144, min: None }), Some(GcPreset::Zgc), &[]);
+        assert!(instance.args.is_empty());
+        assert!(instance.validate(17).is_ok());
+    }
+
+    #[test]
+    fn rejects_conflicting_flags() {
+        let conflicts = [
+            JvmSettings { gc: GcPreset::Aikar, args: args(&["-XX:+UseZGC"]), ..JvmSettings::default() },
+            JvmSettings { args: args(&["-XX:+UseParallelGC", "-XX:+UseSerialGC"]), ..JvmSettings::default() },
+            JvmSettings { memory: MemorySetting::Fixed { max: 2048, min: None }, args: args(&["-Xmx4G"]), ..JvmSettings::default() },
+            JvmSettings { memory: MemorySetting::Fixed { max: 2048, min: Some(4096) }, ..JvmSettings::default() },
+            JvmSettings { args: args(&["-Xms4G", "-Xmx2G"]), ..JvmSettings::default() },
+            JvmSettings { args: args(&["-Xmx2G", "-Xmx4G"]), ..JvmSettings::default() },
+        ];
+        for settings in conflicts {
+            assert!(matches!(settings.validate(17), Err(CoreError::JvmArguments(_))), "{:?}", settings);
+        }
+        // 与预设相同的回收器不算冲突
+        let same = JvmSettings { gc: GcPreset::G1, args: args(&["-XX:+UseG1GC"]), ..JvmSettings::default() };
+        assert!(same.validate(17).is_ok());
+    }
+}
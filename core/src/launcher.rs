@@ -1,323 +1,511 @@
 use std::env::consts::{OS};
 use std::{env};
-use std::fs::{File, read_dir, remove_file};
-use std::io::{Read, Write};
+use std::fs::{File, remove_file};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use serde_json::Value;
-use crate::core::{Asset, check_rule, merge_json, name_to_path};
+use std::process::{Child, Command};
+use crate::account::Account;
+use crate::error::missing;
+use crate::java::{is_compatible, probe_cached, required_major_version, select, JavaInstallation};
+use crate::jvm::JvmSettings;
+use crate::template::Template;
+use crate::yggdrasil::{authlib_injector, cached_authlib_injector, javaagent_argument};
+use crate::{Asset, CoreError, Result};
+use crate::model::Argument;
+use crate::natives::{extract_all, resolve, NativeKind};
+use crate::resolver::resolve_version;
+use crate::rule::{argument_values, is_allowed, Features, Platform};
+
+// 快速游戏：启动后直接进入世界、服务器或Realms
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QuickPlay {
+    Singleplayer(String),
+    Multiplayer(String),
+    Realms(String),
+}
+
+// 启动选项
+#[derive(Clone, Debug, Default)]
+pub struct LaunchOptions {
+    // 游戏目录，默认与dir相同（版本隔离时不同）
+    pub game_dir: Option<PathBuf>,
+    // 窗口大小
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // 试玩模式
+    pub demo: bool,
+    pub quick_play: Option<QuickPlay>,
+    // 内存、垃圾回收器和自定义JVM参数
+    pub jvm: JvmSettings,
+    // 指定的爪哇，没有时按版本要求自动选择
+    pub java: Option<PathBuf>,
+}
+
+impl LaunchOptions {
+    // 参数规则里的features
+    pub fn features(&self) -> Features {
+        Features {
+            is_demo_user: self.demo,
+            has_custom_resolution: self.width.is_some() && self.height.is_some(),
+            has_quick_plays_support: self.quick_play.is_some(),
+            is_quick_play_singleplayer: matches!(self.quick_play, Some(QuickPlay::Singleplayer(_))),
+            is_quick_play_multiplayer: matches!(self.quick_play, Some(QuickPlay::Multiplayer(_))),
+            is_quick_play_realms: matches!(self.quick_play, Some(QuickPlay::Realms(_))),
+        }
+    }
+}
+
+// 替换好变量的启动命令
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LaunchCommand {
+    pub java: PathBuf,
+    pub arguments: Vec<String>,
+    // 游戏进程的工作目录
+    pub game_dir: PathBuf,
+}
+
+impl LaunchCommand {
+    // 返回游戏进程，由调用者决定是否等待和如何处理输出
+    pub fn spawn(&self) -> Result<Child> {
+        Command::new(&self.java)
+            .args(&self.arguments)
+            .current_dir(&self.game_dir)
+            .spawn()
+            .map_err(|e| CoreError::Java(format!("Could not execute Minecraft: {}", e)))
+    }
+}
 
 pub fn launch(
     name: &str,
     dir: &Path,
-    java: &Path) -> Result<(), String> {
+    javas: &[JavaInstallation],
+    account: &Account,
+    options: &LaunchOptions) -> Result<Child> {
     // 御坂美琴生日快乐！
+    prepare(name, dir, javas, account, options)?.spawn()
+}
+
+// 检查文件、选择爪哇并生成启动命令，Native库也在这里解压
+pub fn prepare(
+    name: &str,
+    dir: &Path,
+    javas: &[JavaInstallation],
+    account: &Account,
+    options: &LaunchOptions) -> Result<LaunchCommand> {
 
-    // TODO: 我们是不是要支持下模组加载器？
-    // TODO: 哼哼哼啊啊啊啊我做不到啊啊啊啊啊啊啊
     // 文件夹检查函数
     fn check_dir(dir: &Path) -> bool {
-        if !dir.exists() || !dir.is_dir() {
-            return false;
-        }
-        return true;
+        dir.exists() && dir.is_dir()
     }
 
     // 转换为绝对路径
     fn to_absolute(path: &Path) -> PathBuf {
-        let temp = env::current_dir().unwrap().join(path);
-        return temp;
+        match env::current_dir() {
+            Ok(current) => current.join(path),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    // 路径转换为字符串
+    fn path_str(path: &Path) -> Result<&str> {
+        path.to_str().ok_or_else(|| CoreError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Path {:?} is not valid UTF-8!", path))))
     }
 
     // 路径检查
-    let temp = dir.clone().join("versions").join(name);
+    let temp = dir.join("versions").join(name);
     let temp = to_absolute(temp.as_path());
     let versions_dir = temp.as_path();
     if !check_dir(versions_dir) {
-        return Err(format!("Could not find version \"{}\"!", name));
+        return Err(CoreError::MissingVersion(String::from(name)));
     }
-    let temp = dir.clone().join("libraries");
+    let temp = dir.join("libraries");
     let temp = to_absolute(temp.as_path());
     let library_dir = temp.as_path();
     if !check_dir(library_dir) {
-        return Err("Could not find libraries!".parse().unwrap())
+        return Err(CoreError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "Could not find libraries!")));
     }
-    let temp = dir.clone().join("assets");
+    let temp = dir.join("assets");
     let temp = to_absolute(temp.as_path());
     let asset_dir = temp.as_path();
     if !check_dir(asset_dir) {
-        return Err("Could not find libraries!".parse().unwrap())
+        return Err(CoreError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "Could not find assets!")));
     }
 
-    // JSON文件的读取
-    let mut json = File::open(versions_dir.clone().join(format!("{}.json", name))).expect("Could not open JSON!");
-    let temp: &mut String = &mut String::new();
-    json.read_to_string(temp).expect("Could not read JSON!");
-    let json: Value = serde_json::from_str(temp.as_str()).expect("JSON format error!");
+    // JSON文件的读取，按inheritsFrom合并所有依赖的版本
+    let versions_root = versions_dir.parent().ok_or_else(|| CoreError::MissingVersion(String::from(name)))?;
+    let resolved = resolve_version(versions_root, name)?;
+    let json = &resolved.version;
 
-    // 是否包含依赖的版本
-    let has_inherit = json.get("inheritsFrom").is_some();
-    let mut inherit_id = String::new();
-    let json: Value = if has_inherit {
-        // 通过ID获取已安装的游戏
-        let inherits_from = json["inheritsFrom"].as_str().unwrap();
-        let versions_dir = versions_dir.clone().parent().unwrap();
-        let mut inherits = String::new();
-        let mut inherits_json: Value = "{}".parse().unwrap();
-        for i in read_dir(versions_dir.clone()).unwrap() {
-            let name = i.unwrap().file_name();
-            let name = name.to_str().unwrap();
-            let path = versions_dir.clone().join(name).join(format!("{}.json", name));
-            if path.exists() && path.is_file() {
-                inherit_id = String::from(name);
-                let mut file = File::open(versions_dir.clone().join(name).join(format!("{}.json", name))).expect("Could not open file!");
-                let mut buf = String::new();
-                file.read_to_string(&mut buf).unwrap();
-                let buf: Value = serde_json::from_str(buf.as_str()).unwrap();
-                let id = buf["id"].as_str().unwrap();
-                if id == inherits_from {
-                    inherits = String::from(id);
-                    inherits_json = buf;
-                    break;
-                }
+    // 使用指定的爪哇，没有指定时根据版本要求选择
+    let required = required_major_version(json);
+    let specified;
+    let java = match &options.java {
+        Some(path) => {
+            let info = probe_cached(path)?;
+            // 指定的爪哇也要满足版本要求
+            if !is_compatible(required, info.major_version) {
+                return Err(CoreError::NoCompatibleJava(required));
             }
+            specified = JavaInstallation { path: path.clone(), info };
+            &specified
         }
-        if inherits == String::new() {
-            return Err(String::from("Could not find inheritsFrom for version!"));
-        }
-
-        // 如果找到版本，则合并版本文件
-        merge_json(json.clone(), inherits_json).unwrap()
-    } else {
-        json
+        None => select(javas, required)?,
     };
 
-    if !dir.clone().join("assets/logging.xml").exists() {
-        let mut x = File::create(dir.clone().join("assets/logging.xml")).unwrap();
-        x.write(&*Asset::get("logging.xml").unwrap().data).unwrap();
+    // 启动参数（最后统一替换${...}变量）
+    let mut arguments: Vec<String> = vec![
+        // JVM参数
+        // Native库
+        String::from("-Djava.library.path=${natives_directory}"),
+        String::from("-Dminecraft.client.jar=${primary_jar}"),
+        // Log4j修复
+        String::from("-Djava.rmi.server.useCodebaseOnly=true"),
+        String::from("-Dcom.sun.jndi.rmi.object.trustURLCodebase=false"),
+        String::from("-Dcom.sun.jndi.cosnaming.object.trustURLCodebase=false"),
+        String::from("-Dlog4j2.formatMsgNoLookups=true"),
+    ];
+    // 日志配置，使用自带的修复了Log4Shell的配置代替版本提供的配置
+    if let Some(logging) = json.logging.as_ref().and_then(|l| l.client.as_ref()) {
+        let config = asset_dir.join("logging.xml");
+        if !config.exists() {
+            let mut file = File::create(&config)?;
+            if let Some(logging) = Asset::get("logging.xml") {
+                file.write_all(&logging.data)?;
+            }
+        }
+        arguments.push(Template::new().with("path", path_str(&config)?).render(&logging.argument)?);
+    }
+    // 外置登录
+    if let Account::Yggdrasil(account) = account {
+        // 已经下载过时不访问网络，需要更新时另外调用authlib_injector
+        let authlib_dir = to_absolute(dir.join("authlib-injector").as_path());
+        let jar = match cached_authlib_injector(&authlib_dir) {
+            Some(jar) => jar,
+            None => authlib_injector(&authlib_dir)?,
+        };
+        arguments.push(javaagent_argument(&jar, &account.server));
     }
-
-    // 替换游戏参数的函数
-    let replace_jvm_argument = |arg: String| -> String {
-        let arg = arg;
-
-        // 必要参数（迫真
-        // 非必要参数（迫真
-        let arg = arg.replace("${launcher_name}", "Akiraka");
-        let arg = arg.replace("${launcher_version}", "internal");
-        return arg;
-    };
-
-    // 替换游戏参数的函数
-    let replace_game_argument = |arg: String| -> String {
-        let arg = arg;
-        // TODO: 替换游戏参数
-        // --username
-        // ${auth_player_name}
-        // --version
-        // ${version_name}
-        // --gameDir
-        // ${game_directory}
-        // --assetsDir
-        // ${assets_root}
-        // ${game_assets}
-        // --assetIndex
-        // ${assets_index_name}
-        // --uuid
-        // ${auth_uuid}
-        // --accessToken
-        // ${auth_access_token}
-        // ${auth_session}
-        // --userType
-        // ${user_type}
-        // --versionType
-        // ${version_type}
-        // --userProperties
-        // ${user_properties}
-
-        // 必要参数（迫真
-        let arg = arg.replace("${game_directory}", to_absolute(dir).to_str().unwrap());
-        let arg = arg.replace("${assets_root}", to_absolute(dir.clone().join("assets").as_path()).to_str().unwrap());
-        let arg = arg.replace("${game_assets}", to_absolute(dir.clone().join("assets").as_path()).to_str().unwrap());
-        let arg = arg.replace("${assets_index_name}", json["assets"].as_str().unwrap());
-        let arg = arg.replace("${user_properties}", "{}");
-        // TODO: 账号
-        let arg = arg.replace("${auth_uuid}", "00000000-0000-0000-0000-000000000000");
-        let arg = arg.replace("${auth_player_name}", "Dev");
-        let arg = arg.replace("${auth_access_token}", "null");
-        let arg = arg.replace("${auth_session}", "null");
-        let arg = arg.replace("${clientid}", "null");
-        let arg = arg.replace("${auth_xuid}", "null");
-        let arg = arg.replace("${user_type}", "msa");
-        // 非必要参数（迫真
-        let arg = arg.replace("${version_type}", "akiraka");
-        let arg = arg.replace("${version_name}", "vanilla");
-        return arg;
-    };
-
-    // 启动参数
-    let mut arguments: Vec<String> = Vec::new();
-
-    // JVM参数
-    // Native库
-    arguments.push(format!("-Djava.library.path={}", to_absolute(versions_dir.clone().join("natives").as_path()).to_str().unwrap()));
-    arguments.push(String::from(r"-Dminecraft.client.jar=.minecraft\versions\1.14.4\1.14.4.jar"));
-    // Log4j修复
-    arguments.push(String::from("-Djava.rmi.server.useCodebaseOnly=true"));
-    arguments.push(String::from("-Dcom.sun.jndi.rmi.object.trustURLCodebase=false"));
-    arguments.push(String::from("-Dcom.sun.jndi.cosnaming.object.trustURLCodebase=false"));
-    arguments.push(String::from("-Dlog4j2.formatMsgNoLookups=true"));
 
     // 游戏提供的参数
     // 检查是否需要跳过
-    fn should_skip(arg: String) -> bool {
+    fn should_skip(arg: &str) -> bool {
         let mut res = false;
-        res |= arg == String::from("-cp");
-        res |= arg == String::from("${classpath}");
-        if arg.find("=").is_some() {
+        res |= arg == "-cp";
+        res |= arg == "${classpath}";
+        if arg.contains('=') {
             res |= arg.starts_with("-Djava.library.path");
             res |= arg.starts_with("-Dos.name");
             res |= arg.starts_with("-Dos.version");
         }
-        return res;
+        res
     }
-    // 添加参数
-    if json.get("arguments").is_some() {
-        if json["arguments"].get("jvm").is_some() {
-            for i in json["arguments"]["jvm"].as_array().unwrap() {
-                if i.is_string() {
-                    if should_skip(String::from(i.as_str().unwrap())) {
-                        continue
-                    }
-                    arguments.push(replace_jvm_argument(String::from(i.as_str().unwrap())));
-                } else {
-                    if i.get("rules").is_some() {
-                        if !check_rule(i["rules"].as_array().unwrap()) {
-                            continue
-                        }
-                        if i["value"].is_string() {
-                            if should_skip(String::from(i["value"].as_str().unwrap())) {
-                                continue
-                            }
-                            arguments.push(replace_jvm_argument(String::from(i["value"].as_str().unwrap())));
-                        } else if i["value"].is_array() {
-                            for j in i["value"].as_array().unwrap() {
-                                if should_skip(String::from(j.as_str().unwrap())) {
-                                    continue
-                                }
-                                arguments.push(replace_jvm_argument(String::from(j.as_str().unwrap())));
-                            }
-                        }
-                    }
-                }
+    // 添加参数，带规则的参数只在规则允许时添加
+    let platform = Platform::current();
+    let features = options.features();
+    let add_argument = |arguments: &mut Vec<String>, i: &Argument, skip: fn(&str) -> bool| {
+        for arg in argument_values(i, platform, &features) {
+            if !skip(arg) {
+                arguments.push(arg.clone());
             }
         }
+    };
+    if let Some(args) = &json.arguments {
+        for i in &args.jvm {
+            add_argument(&mut arguments, i, should_skip);
+        }
     }
+    // 内存和用户的JVM参数，放在游戏提供的参数后面以便覆盖
+    let game_directory = to_absolute(options.game_dir.as_deref().unwrap_or(dir));
+    arguments.extend(options.jvm.arguments(&game_directory, java.info.major_version)?);
     // Classpath参数
-    // TODO: 使用依赖名获取路径（支持Fabric和Quilt）
-    #[cfg(target_os = "windows")]
-        let path_separator = ";";
-    #[cfg(not(target_os = "windows"))]
-        let path_separator = ":";
-    let temp = versions_dir.join(format!("{}.jar", name));
-    let temp = to_absolute(temp.as_path());
+    let path_separator = if OS == "windows" { ";" } else { ":" };
+    // 游戏主文件在继承链最底层的版本里
+    let jar = resolved.jar();
+    let primary_jar = to_absolute(versions_root.join(jar).join(format!("{}.jar", jar)).as_path());
 
-    if temp.exists() && File::open(temp.clone()).unwrap().metadata().unwrap().len() == 0 {
-        remove_file(temp.as_path()).unwrap();
+    if primary_jar.exists() && primary_jar.metadata()?.len() == 0 {
+        remove_file(primary_jar.as_path())?;
     }
 
-    let mut classpath = if temp.exists() && !has_inherit{
-        String::from(temp.as_path().to_str().unwrap())
+    let mut classpath = if primary_jar.exists() {
+        String::from(path_str(primary_jar.as_path())?)
     } else {
         String::new()
     };
 
-    if has_inherit {
-        classpath += path_separator;
-        classpath += to_absolute(versions_dir.clone().parent().unwrap().join(inherit_id.clone()).join(format!("{}.jar", inherit_id.clone())).as_path()).to_str().unwrap();
-    }
-
-    for i in json["libraries"].as_array().unwrap() {
+    for i in &json.libraries {
         // 检查规则
-        let allow = if i.get("rules").is_some() {
-            check_rule(i["rules"].as_array().unwrap())
-        } else {
-            true
-        };
-        if !allow {
+        if !is_allowed(i.rules.as_deref(), platform, &Features::default()) {
             continue;
         }
 
-        // TODO: Path by name
-        if i.get("downloads").is_some() {
-            if i["downloads"].get("artifact").is_some() {
-                classpath += path_separator;
-                classpath += library_dir.clone().join(i["downloads"]["artifact"]["path"].as_str().unwrap()).to_str().unwrap();
-            }
-            if i["downloads"].get("classifiers").is_some() {
-                let natives = &i["natives"];
-                // 系统
-                let os = if OS == "macos" {
-                    "osx"
-                } else {
-                    OS
-                };
-                if natives.get(os).is_some() {
-                    let natives = &i["downloads"]["classifiers"][&i["natives"][os].as_str().unwrap()];
-                    if natives.is_null() {
-                        continue
-                    }
-                    classpath += path_separator;
-                    classpath += library_dir.clone().join(natives["path"].as_str().unwrap()).to_str().unwrap();
-                }
-            }
-        } else {
+        // 没有给出路径时按Maven坐标计算
+        if let Some(artifact) = i.artifact_download()? {
+            let path = match artifact.path {
+                Some(path) => path,
+                None => i.coordinate()?.path(),
+            };
             classpath += path_separator;
-            classpath += library_dir.clone().join(name_to_path(String::from(i["name"].as_str().unwrap()))).to_str().unwrap();
+            classpath += path_str(&library_dir.join(path))?;
         }
     }
+    // 旧格式的Native库也放进classpath，与解压时使用相同的分类（包括${arch}）
+    let natives = resolve(&json.libraries, platform);
+    for native in natives.iter().filter(|n| n.kind == NativeKind::Classifier) {
+        classpath += path_separator;
+        classpath += path_str(&library_dir.join(native.path()?))?;
+    }
     arguments.push(String::from("-cp"));
-    arguments.push(classpath.clone());
+    arguments.push(String::from("${classpath}"));
 
     // 主类
-    arguments.push(String::from(json["mainClass"].as_str().unwrap()));
+    arguments.push(json.main_class.clone().ok_or_else(|| missing("mainClass"))?);
 
     // 游戏参数
-    if json.get("arguments").is_some() {
+    if let Some(args) = &json.arguments {
         // 扁平化后的参数
-        let args = json["arguments"]["game"].as_array().unwrap();
-        for i in args {
-            if i.as_str().is_none() {
-                continue
-            }
-            let temp = i.as_str().unwrap();
-            let arg = replace_game_argument(String::from(temp));
-            arguments.push(arg);
+        for i in &args.game {
+            add_argument(&mut arguments, i, |_| false);
         }
-    } else if json.get("minecraftArguments").is_some() {
-        // TODO: 扁平化前的参数
-        for i in json["minecraftArguments"].as_str().unwrap().split(" ") {
-            arguments.push(replace_game_argument(String::from(i)));
+    } else if let Some(minecraft_arguments) = &json.minecraft_arguments {
+        // 扁平化前的参数
+        for i in minecraft_arguments.split(' ').filter(|i| !i.is_empty()) {
+            arguments.push(String::from(i));
         }
     }
 
-    // TODO: 调试
-    // for i in &arguments {
-    //     println!("{}", i);
-    // }
-    let mut x: Vec<&str> = classpath.split(";").collect();
-    x.sort();
-    for i in x {
-        println!("{}", i.replace("/", "\\"));
+    // Native库，每次启动都从依赖库目录重新解压
+    let natives_dir = to_absolute(versions_dir.join("natives").as_path());
+    extract_all(&natives, library_dir, &natives_dir)?;
+
+    // 替换所有${...}变量
+    let assets_index_name = json.assets.clone().ok_or_else(|| missing("assets"))?;
+    // 旧版本的资源目录
+    let game_assets = match assets_index_name.as_str() {
+        "legacy" => asset_dir.join("virtual").join("legacy"),
+        "pre-1.6" => game_directory.join("resources"),
+        _ => asset_dir.to_path_buf(),
+    };
+    let mut template = Template::new();
+    template
+        // 账号
+        .set("auth_player_name", account.name())
+        .set("auth_uuid", account.uuid())
+        .set("auth_access_token", account.access_token())
+        .set("auth_session", match account {
+            Account::Offline { .. } => String::from(account.access_token()),
+            _ => format!("token:{}:{}", account.access_token(), account.uuid()),
+        })
+        .set("auth_xuid", account.xuid())
+        .set("clientid", account.client_id())
+        .set("user_type", account.user_type())
+        .set("user_properties", "{}")
+        // 版本
+        .set("version_name", name)
+        .set("version_type", json.version_type.as_deref().unwrap_or("release"))
+        .set("assets_index_name", assets_index_name.as_str())
+        .set("primary_jar", path_str(&primary_jar)?)
+        // 目录
+        .set("game_directory", path_str(&game_directory)?)
+        .set("assets_root", path_str(asset_dir)?)
+        .set("game_assets", path_str(&game_assets)?)
+        .set("library_directory", path_str(library_dir)?)
+        .set("natives_directory", path_str(&natives_dir)?)
+        .set("classpath", classpath.as_str())
+        .set("classpath_separator", path_separator)
+        // 启动器
+        .set("launcher_name", "Akiraka")
+        .set("launcher_version", env!("CARGO_PKG_VERSION"))
+        // 窗口
+        .set("resolution_width", options.width.unwrap_or(854).to_string())
+        .set("resolution_height", options.height.unwrap_or(480).to_string());
+    // 快速游戏
+    if let Some(quick_play) = &options.quick_play {
+        let log = game_directory.join("quickPlay").join("log.json");
+        template.set("quickPlayPath", path_str(&log)?);
+        match quick_play {
+            QuickPlay::Singleplayer(world) => template.set("quickPlaySingleplayer", world.as_str()),
+            QuickPlay::Multiplayer(server) => template.set("quickPlayMultiplayer", server.as_str()),
+            QuickPlay::Realms(realm) => template.set("quickPlayRealms", realm.as_str()),
+        };
     }
+    Ok(LaunchCommand {
+        java: java.path.clone(),
+        arguments: template.render_all(&arguments)?,
+        game_dir: game_directory,
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use serde_json::{json, Value};
+    use crate::auth::MicrosoftAccount;
+    use crate::java::JavaInfo;
 
-    let proc = Command::new(java)
-        .args(arguments)
-        .current_dir(dir)
-        .spawn()
-        .expect("Could not execute Minecraft!");
+    // 与官方1.20.1相同的参数
+    fn modern_version() -> Value {
+        let feature = |name: &str| json!([{"action": "allow", "features": {name: true}}]);
+        json!({
+            "id": "1.20.1",
+            "type": "release",
+            "mainClass": "net.minecraft.client.main.Main",
+            "assets": "5",
+            "javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17},
+            "logging": {"client": {
+                "argument": "-Dlog4j.configurationFile=${path}",
+                "file": {"id": "client-1.12.xml", "sha1": "bd65e7d2e3c237be76cfbef4c2405033d7f91521", "size": 888, "url": "https://piston-data.mojang.com/v1/objects/bd65e7d2e3c237be76cfbef4c2405033d7f91521/client-1.12.xml"},
+                "type": "log4j2-xml"
+            }},
+            "arguments": {
+                "game": [
+                    "--username", "${auth_player_name}", "--version", "${version_name}",
+                    "--gameDir", "${game_directory}", "--assetsDir", "${assets_root}",
+                    "--assetIndex", "${assets_index_name}", "--uuid", "${auth_uuid}",
+                    "--accessToken", "${auth_access_token}", "--clientId", "${clientid}",
+                    "--xuid", "${auth_xuid}", "--userType", "${user_type}", "--versionType", "${version_type}",
+                    {"rules": feature("is_demo_user"), "value": "--demo"},
+                    {"rules": feature("has_custom_resolution"), "value": ["--width", "${resolution_width}", "--height", "${resolution_height}"]},
+                    {"rules": feature("has_quick_plays_support"), "value": ["--quickPlayPath", "${quickPlayPath}"]},
+                    {"rules": feature("is_quick_play_singleplayer"), "value": ["--quickPlaySingleplayer", "${quickPlaySingleplayer}"]},
+                    {"rules": feature("is_quick_play_multiplayer"), "value": ["--quickPlayMultiplayer", "${quickPlayMultiplayer}"]},
+                    {"rules": feature("is_quick_play_realms"), "value": ["--quickPlayRealms", "${quickPlayRealms}"]}
+                ],
+                "jvm": [
+                    {"rules": [{"action": "allow", "os": {"name": "osx"}}], "value": ["-XstartOnFirstThread"]},
+                    {"rules": [{"action": "allow", "os": {"name": "windows"}}], "value": "-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump"},
+                    {"rules": [{"action": "allow", "os": {"arch": "x86"}}], "value": "-Xss1M"},
+                    "-Djava.library.path=${natives_directory}",
+                    "-Djna.tmpdir=${natives_directory}",
+                    "-Dorg.lwjgl.system.SharedLibraryExtractPath=${natives_directory}",
+                    "-Dio.netty.native.workdir=${natives_directory}",
+                    "-Dminecraft.launcher.brand=${launcher_name}",
+                    "-Dminecraft.launcher.version=${launcher_version}",
+                    "-cp", "${classpath}"
+                ]
+            },
+            "libraries": [{
+                "name": "com.mojang:logging:1.1.1",
+                "downloads": {"artifact": {"path": "com/mojang/logging/1.1.1/logging-1.1.1.jar", "url": "https://libraries.minecraft.net/com/mojang/logging/1.1.1/logging-1.1.1.jar"}}
+            }]
+        })
+    }
 
-    let stdout = proc.wait_with_output().unwrap().stdout;
-    println!("{}", String::from_utf8(stdout).unwrap());
-    Ok(())
+    // 与官方1.12.2相同的参数
+    fn legacy_version() -> Value {
+        json!({
+            "id": "1.12.2",
+            "type": "release",
+            "mainClass": "net.minecraft.client.main.Main",
+            "assets": "1.12",
+            "javaVersion": {"component": "jre-legacy", "majorVersion": 8},
+            "minecraftArguments": "--username ${auth_player_name} --version ${version_name} --gameDir ${game_directory} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --uuid ${auth_uuid} --accessToken ${auth_access_token} --userType ${user_type} --versionType ${version_type}",
+            "libraries": [{
+                "name": "com.mojang:patchy:1.3.9",
+                "downloads": {"artifact": {"path": "com/mojang/patchy/1.3.9/patchy-1.3.9.jar", "url": "https://libraries.minecraft.net/com/mojang/patchy/1.3.9/patchy-1.3.9.jar"}}
+            }]
+        })
+    }
+
+    // 带有版本Json的游戏目录
+    fn game_dir(name: &str, versions: &[Value]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("akiraka-launcher-{}-{}", name, std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(dir.join("assets")).unwrap();
+        create_dir_all(dir.join("libraries")).unwrap();
+        for version in versions {
+            let id = version["id"].as_str().unwrap();
+            let version_dir = dir.join("versions").join(id);
+            create_dir_all(&version_dir).unwrap();
+            write(version_dir.join(format!("{}.json", id)), version.to_string()).unwrap();
+        }
+        dir
+    }
+
+    fn java(major_version: u32) -> JavaInstallation {
+        JavaInstallation {
+            path: PathBuf::from("/usr/bin/java"),
+            info: JavaInfo {
+                home: PathBuf::from("/usr"),
+                major_version,
+                version: format!("{}.0.1", major_version),
+                vendor: String::from("Eclipse Adoptium"),
+                arch: String::from("amd64"),
+                is_64bit: true,
+            },
+        }
+    }
+
+    fn value_after<'a>(arguments: &'a [String], key: &str) -> &'a str {
+        let index = arguments.iter().position(|a| a == key).unwrap_or_else(|| panic!("{} is missing", key));
+        &arguments[index + 1]
+    }
+
+    #[test]
+    fn renders_every_placeholder() {
+        let dir = game_dir("modern", &[modern_version()]);
+        let account = Account::Microsoft(MicrosoftAccount {
+            name: String::from("Notch"),
+            uuid: String::from("069a79f444e94726a5befca90e38aaf5"),
+            access_token: String::from("token"),
+            refresh_token: String::from("refresh"),
+            expires_at: 0,
+            xuid: String::from("2535405290000000"),
+            client_id: String::from("client"),
+        });
+        let options = LaunchOptions {
+            width: Some(1280),
+            height: Some(720),
+            demo: true,
+            quick_play: Some(QuickPlay::Multiplayer(String::from("example.com"))),
+            ..LaunchOptions::default()
+        };
+        let command = prepare("1.20.1", &dir, &[java(8), java(17)], &account, &options).unwrap();
+        let arguments = &command.arguments;
+        assert!(arguments.iter().all(|a| !a.contains("${")), "{:?}", arguments);
+        assert_eq!(command.java, PathBuf::from("/usr/bin/java"));
+        assert_eq!(value_after(arguments, "--xuid"), "2535405290000000");
+        assert_eq!(value_after(arguments, "--clientId"), "client");
+        assert_eq!(value_after(arguments, "--userType"), "msa");
+        assert_eq!(value_after(arguments, "--width"), "1280");
+        assert_eq!(value_after(arguments, "--quickPlayMultiplayer"), "example.com");
+        assert!(arguments.contains(&String::from("--demo")));
+        assert!(arguments.contains(&String::from("-Dminecraft.launcher.brand=Akiraka")));
+        assert!(value_after(arguments, "-cp").contains("com/mojang/logging/1.1.1/logging-1.1.1.jar"));
+        assert!(arguments.iter().any(|a| a.starts_with("-Dlog4j.configurationFile=") && a.ends_with("logging.xml")));
+        assert!(dir.join("assets/logging.xml").is_file());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn checks_specified_java() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = game_dir("specified-java", &[modern_version(), legacy_version()]);
+        let script = dir.join("java");
+        std::fs::write(&script, "#!/bin/sh\necho '    java.home = /fake' >&2\necho '    java.version = 1.8.0_382' >&2\necho '    java.vendor = Fake' >&2\necho '    os.arch = amd64' >&2\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let options = LaunchOptions { java: Some(script.clone()), ..LaunchOptions::default() };
+        // 实例指定的爪哇8不能启动需要爪哇17的版本，即使列表里有合适的爪哇
+        assert!(matches!(
+            prepare("1.20.1", &dir, &[java(17)], &Account::offline("Steve"), &options),
+            Err(CoreError::NoCompatibleJava(17))
+        ));
+        let command = prepare("1.12.2", &dir, &[java(17)], &Account::offline("Steve"), &options).unwrap();
+        assert_eq!(command.java, script);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn renders_legacy_arguments() {
+        let dir = game_dir("legacy", &[legacy_version()]);
+        let command = prepare("1.12.2", &dir, &[java(8)], &Account::offline("Steve"), &LaunchOptions::default()).unwrap();
+        let arguments = &command.arguments;
+        assert!(arguments.iter().all(|a| !a.contains("${")), "{:?}", arguments);
+        assert_eq!(value_after(arguments, "--username"), "Steve");
+        assert_eq!(value_after(arguments, "--userType"), "legacy");
+        assert_eq!(value_after(arguments, "--assetIndex"), "1.12");
+        assert_eq!(command.game_dir, dir);
+        remove_dir_all(&dir).unwrap();
+    }
 }
\ No newline at end of file
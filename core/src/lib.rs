@@ -1,10 +1,44 @@
+use rust_embed::RustEmbed;
+
+pub mod account;
+pub mod auth;
+pub mod config;
+pub mod download;
+pub mod error;
+pub mod forge;
+pub mod install;
+pub mod instance;
+pub mod java;
+pub mod jvm;
+pub mod launcher;
+pub mod loader;
+pub mod maven;
+pub mod model;
+pub mod natives;
+pub mod network;
+pub mod progress;
+pub mod resolver;
+pub mod rule;
+pub mod runtime;
+pub mod scheduler;
+pub mod source;
+pub mod template;
+pub mod util;
+pub mod vault;
+pub mod yggdrasil;
 #[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
+mod test_server;
+
+pub use error::{CoreError, Result};
+
+#[derive(RustEmbed)]
+#[folder = "assets"]
+pub struct Asset;
+
+// 御坂美琴生日快乐！
+pub struct VersionSource {
+    pub version_id: String,
+    pub version_url: String,
+    pub version_type: String,
+    pub release_time: String
 }
@@ -0,0 +1,268 @@
+use std::fs::{create_dir_all, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use crate::install::library_jobs;
+use crate::model::Version;
+use crate::progress::{CancelToken, InstallPhase, ProgressSink, Tracker};
+use crate::scheduler::Scheduler;
+use crate::source::{DownloadSources, FABRIC_META, QUILT_META};
+use crate::{CoreError, Result};
+
+// 使用meta接口安装的模组加载器
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoaderKind {
+    Fabric,
+    Quilt,
+}
+
+impl LoaderKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoaderKind::Fabric => "Fabric",
+            LoaderKind::Quilt => "Quilt",
+        }
+    }
+
+    fn default_meta(&self) -> &'static str {
+        match self {
+            LoaderKind::Fabric => FABRIC_META,
+            LoaderKind::Quilt => QUILT_META,
+        }
+    }
+
+    // 接口版本
+    fn api(&self) -> &'static str {
+        match self {
+            LoaderKind::Fabric => "v2",
+            LoaderKind::Quilt => "v3",
+        }
+    }
+}
+
+// 加载器版本，Quilt没有stable字段
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct LoaderVersion {
+    pub version: String,
+    pub maven: String,
+    #[serde(default)]
+    pub stable: bool,
+}
+
+#[derive(Deserialize)]
+struct LoaderEntry {
+    loader: LoaderVersion,
+}
+
+// 支持的游戏版本
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct GameVersion {
+    pub version: String,
+    #[serde(default)]
+    pub stable: bool,
+}
+
+pub struct LoaderInstaller {
+    kind: LoaderKind,
+    meta_url: String,
+    sources: DownloadSources,
+    client: Client,
+    pool_size: usize,
+}
+
+impl LoaderInstaller {
+    pub fn new(kind: LoaderKind) -> LoaderInstaller {
+        LoaderInstaller {
+            kind,
+            meta_url: String::from(kind.default_meta()),
+            sources: DownloadSources::official(),
+            client: Client::new(),
+            pool_size: 8,
+        }
+    }
+
+    pub fn fabric() -> LoaderInstaller {
+        LoaderInstaller::new(LoaderKind::Fabric)
+    }
+
+    pub fn quilt() -> LoaderInstaller {
+        LoaderInstaller::new(LoaderKind::Quilt)
+    }
+
+    // meta接口的根地址
+    pub fn with_meta_url(mut self, url: impl Into<String>) -> LoaderInstaller {
+        self.meta_url = url.into();
+        self
+    }
+
+    pub fn with_sources(mut self, sources: DownloadSources) -> LoaderInstaller {
+        self.sources = sources;
+        self
+    }
+
+    pub fn with_client(mut self, client: Client) -> LoaderInstaller {
+        self.client = client;
+        self
+    }
+
+    pub fn with_pool_size(mut self, pool_size: usize) -> LoaderInstaller {
+        self.pool_size = pool_size;
+        self
+    }
+
+    pub fn kind(&self) -> LoaderKind {
+        self.kind
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}/{}", self.meta_url.trim_end_matches('/'), self.kind.api(), path)
+    }
+
+    // 加载器支持的游戏版本
+    pub fn game_versions(&self) -> Result<Vec<GameVersion>> {
+        Ok(serde_json::from_str(&self.sources.get_text(&self.client, &self.url("versions/game"))?)?)
+    }
+
+    // 某个游戏版本可以使用的加载器版本，最新的在前面
+    pub fn loader_versions(&self, game_version: &str) -> Result<Vec<LoaderVersion>> {
+        let url = self.url(&format!("versions/loader/{}", game_version));
+        let entries: Vec<LoaderEntry> = serde_json::from_str(&self.sources.get_text(&self.client, &url)?)?;
+        Ok(entries.into_iter().map(|e| e.loader).collect())
+    }
+
+    // 启动器使用的版本Json
+    pub fn profile(&self, game_version: &str, loader_version: &str) -> Result<String> {
+        let url = self.url(&format!("versions/loader/{}/{}/profile/json", game_version, loader_version));
+        self.sources.get_text(&self.client, &url)
+    }
+
+    // 写入版本Json并下载依赖库，返回新版本的ID，原版需要已经安装
+    pub fn install(
+        &self,
+        game_version: &str,
+        loader_version: &str,
+        dir: &Path,
+        progress: Arc<dyn ProgressSink>,
+        cancel: &CancelToken) -> Result<String> {
+        let tracker = Tracker::new(progress);
+        tracker.phase(InstallPhase::Json);
+        cancel.check()?;
+        let versions_dir = dir.join("versions");
+        if !versions_dir.join(game_version).join(format!("{}.json", game_version)).is_file() {
+            return Err(CoreError::MissingInheritsFrom(String::from(game_version)));
+        }
+
+        let json = self.profile(game_version, loader_version)?;
+        let version: Version = serde_json::from_str(&json)?;
+        if version.inherits_from.as_deref() != Some(game_version) {
+            return Err(CoreError::Manifest(format!("{} profile {} does not inherit from {}!", self.kind.name(), version.id, game_version)));
+        }
+        let version_dir = versions_dir.join(&version.id);
+        create_dir_all(&version_dir)?;
+        let mut file = File::create(version_dir.join(format!("{}.json", version.id)))?;
+        file.write_all(json.as_bytes())?;
+        file.flush()?;
+
+        // 依赖库都在Maven仓库里
+        tracker.phase(InstallPhase::Libraries);
+        let scheduler = Scheduler::new(self.client.clone(), self.sources.clone()).with_pool_size(self.pool_size);
+        let failed = scheduler.run(library_jobs(&version.libraries, &dir.join("libraries"))?, &tracker, cancel)?;
+        if let Some((_, e)) = failed.into_iter().next() {
+            return Err(e);
+        }
+        tracker.phase(InstallPhase::Done);
+        Ok(version.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{copy, read_to_string, remove_dir_all};
+    use std::path::PathBuf;
+    use crate::progress::NoProgress;
+    use crate::resolver::resolve_version;
+    use crate::source::Mirror;
+    use crate::test_server::{Response, TestServer};
+
+    fn fixture(name: &str) -> String {
+        read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/loader").join(name)).unwrap()
+    }
+
+    fn meta_server() -> TestServer {
+        TestServer::start(|req| {
+            let name = match req.path.as_str() {
+                "/v2/versions/loader/1.20.1" => "fabric-loader-1.20.1.json",
+                "/v2/versions/loader/1.20.1/0.14.21/profile/json" => "fabric-profile-0.14.21-1.20.1.json",
+                "/v3/versions/loader/1.20.1" => "quilt-loader-1.20.1.json",
+                "/v3/versions/loader/1.20.1/0.19.2/profile/json" => "quilt-profile-0.19.2-1.20.1.json",
+                _ => return Response::not_found(),
+            };
+            Response::ok(fixture(name))
+        })
+    }
+
+    // 所有Maven仓库都指向测试服务器
+    fn maven_sources(maven: &TestServer) -> DownloadSources {
+        DownloadSources::new(vec![Arc::new(Mirror::new("Test")
+            .with_rule("https://maven.fabricmc.net", maven.url("/fabric"))
+            .with_rule("https://maven.quiltmc.org/repository/release", maven.url("/quilt")))])
+    }
+
+    fn game_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("akiraka-loader-{}-{}", name, std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(dir.join("versions/1.20.1")).unwrap();
+        copy(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/versions/1.20.1/1.20.1.json"), dir.join("versions/1.20.1/1.20.1.json")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn installs_fabric() {
+        let meta = meta_server();
+        let maven = TestServer::start(|req| if req.path.ends_with(".jar") { Response::ok("jar") } else { Response::not_found() });
+        let installer = LoaderInstaller::fabric().with_meta_url(meta.base_url.clone()).with_sources(maven_sources(&maven));
+
+        let versions = installer.loader_versions("1.20.1").unwrap();
+        assert_eq!(versions.iter().map(|v| (v.version.as_str(), v.stable)).collect::<Vec<_>>(), vec![("0.14.22", true), ("0.14.21", false)]);
+
+        let dir = game_dir("fabric");
+        let id = installer.install("1.20.1", "0.14.21", &dir, Arc::new(NoProgress), &CancelToken::new()).unwrap();
+        assert_eq!(id, "fabric-loader-0.14.21-1.20.1");
+        assert!(dir.join("libraries/net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar").is_file());
+        assert!(dir.join("libraries/net/fabricmc/tiny-mappings-parser/0.3.0+build.17/tiny-mappings-parser-0.3.0+build.17.jar").is_file());
+        assert!(maven.requests().contains(&String::from("GET /fabric/org/ow2/asm/asm/9.5/asm-9.5.jar")));
+
+        // 安装好的版本可以和原版合并
+        let resolved = resolve_version(&dir.join("versions"), &id).unwrap();
+        assert_eq!(resolved.jar(), "1.20.1");
+        assert_eq!(resolved.version.main_class.as_deref(), Some("net.fabricmc.loader.impl.launch.knot.KnotClient"));
+        assert!(resolved.version.asset_index.is_some());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn installs_quilt() {
+        let meta = meta_server();
+        let maven = TestServer::start(|req| if req.path.ends_with(".jar") { Response::ok("jar") } else { Response::not_found() });
+        let installer = LoaderInstaller::quilt().with_meta_url(meta.base_url.clone()).with_sources(maven_sources(&maven));
+
+        let versions = installer.loader_versions("1.20.1").unwrap();
+        assert_eq!(versions[0].maven, "org.quiltmc:quilt-loader:0.19.2");
+
+        let dir = game_dir("quilt");
+        let id = installer.install("1.20.1", "0.19.2", &dir, Arc::new(NoProgress), &CancelToken::new()).unwrap();
+        assert_eq!(id, "quilt-loader-0.19.2-1.20.1");
+        assert!(maven.requests().contains(&String::from("GET /quilt/org/quiltmc/quilt-loader/0.19.2/quilt-loader-0.19.2.jar")));
+        assert!(dir.join("libraries/org/quiltmc/hashed/1.20.1/hashed-1.20.1.jar").is_file());
+
+        // 没有安装原版时不能安装
+        assert!(matches!(
+            installer.install("1.19.4", "0.19.2", &dir, Arc::new(NoProgress), &CancelToken::new()),
+            Err(CoreError::MissingInheritsFrom(id)) if id == "1.19.4"
+        ));
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::{CoreError, Result};
+
+// Maven坐标：group:artifact:version[:classifier][@extension]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MavenCoordinate {
+    pub group: String,
+    pub artifact: String,
+    pub version: String,
+    pub classifier: Option<String>,
+    // 默认为jar
+    pub extension: String,
+}
+
+impl MavenCoordinate {
+    pub fn parse(name: &str) -> Result<MavenCoordinate> {
+        let invalid = || CoreError::Manifest(format!("Invalid library name \"{}\"!", name));
+        let (coordinate, extension) = match name.split_once('@') {
+            Some((coordinate, extension)) if !extension.is_empty() => (coordinate, extension),
+            Some(_) => return Err(invalid()),
+            None => (name, "jar"),
+        };
+        let parts: Vec<&str> = coordinate.split(':').collect();
+        if parts.len() < 3 || parts.len() > 4 || parts.iter().any(|p| p.is_empty()) {
+            return Err(invalid());
+        }
+        Ok(MavenCoordinate {
+            group: String::from(parts[0]),
+            artifact: String::from(parts[1]),
+            version: String::from(parts[2]),
+            classifier: parts.get(3).map(|c| String::from(*c)),
+            extension: String::from(extension),
+        })
+    }
+
+    // artifact-version[-classifier].extension
+    pub fn file_name(&self) -> String {
+        match &self.classifier {
+            Some(classifier) => format!("{}-{}-{}.{}", self.artifact, self.version, classifier, self.extension),
+            None => format!("{}-{}.{}", self.artifact, self.version, self.extension),
+        }
+    }
+
+    // 仓库或者依赖库目录里的相对路径
+    pub fn path(&self) -> String {
+        format!("{}/{}/{}/{}", self.group.replace('.', "/"), self.artifact, self.version, self.file_name())
+    }
+
+    // 在仓库里的地址
+    pub fn url(&self, repository: &str) -> String {
+        format!("{}/{}", repository.trim_end_matches('/'), self.path())
+    }
+}
+
+impl FromStr for MavenCoordinate {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<MavenCoordinate> {
+        MavenCoordinate::parse(s)
+    }
+}
+
+impl Display for MavenCoordinate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.group, self.artifact, self.version)?;
+        if let Some(classifier) = &self.classifier {
+            write!(f, ":{}", classifier)?;
+        }
+        if self.extension != "jar" {
+            write!(f, "@{}", self.extension)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_full_coordinates() {
+        let plain = MavenCoordinate::parse("net.fabricmc:fabric-loader:0.14.21").unwrap();
+        assert_eq!(plain.path(), "net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar");
+        assert_eq!(plain.url("https://maven.fabricmc.net/"), "https://maven.fabricmc.net/net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar");
+
+        let natives = MavenCoordinate::parse("org.lwjgl:lwjgl:3.3.1:natives-windows").unwrap();
+        assert_eq!(natives.classifier.as_deref(), Some("natives-windows"));
+        assert_eq!(natives.path(), "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-windows.jar");
+
+        let zip = MavenCoordinate::parse("de.oceanlabs.mcp:mcp_config:1.20.1-20230612.114412@zip").unwrap();
+        assert_eq!(zip.path(), "de/oceanlabs/mcp/mcp_config/1.20.1-20230612.114412/mcp_config-1.20.1-20230612.114412.zip");
+
+        let both = MavenCoordinate::parse("net.minecraft:client:1.20.1-20230612.114412:mappings@txt").unwrap();
+        assert_eq!(both.file_name(), "client-1.20.1-20230612.114412-mappings.txt");
+        assert_eq!(both.to_string(), "net.minecraft:client:1.20.1-20230612.114412:mappings@txt");
+
+        for invalid in ["a:b", "a::c", "a:b:c@", "a:b:c:d:e"] {
+            assert!(MavenCoordinate::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
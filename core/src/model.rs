@@ -0,0 +1,456 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::maven::MavenCoordinate;
+use crate::source::LIBRARIES;
+use crate::Result;
+
+// 版本Json等文件的类型，不认识的字段放在extra里，写回时原样保留
+
+// version_manifest.json
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionManifest {
+    #[serde(default)]
+    pub latest: Latest,
+    #[serde(default)]
+    pub versions: Vec<ManifestVersion>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Latest {
+    #[serde(default)]
+    pub release: String,
+    #[serde(default)]
+    pub snapshot: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestVersion {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub version_type: String,
+    pub url: String,
+    #[serde(default)]
+    pub time: String,
+    #[serde(default)]
+    pub release_time: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// 版本Json（versions/<id>/<id>.json）
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Version {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inherits_from: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub version_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub main_class: Option<String>,
+    // 1.13以前的参数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minecraft_arguments: Option<String>,
+    // 1.13以后的参数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Arguments>,
+    #[serde(default)]
+    pub libraries: Vec<Library>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_index: Option<AssetIndexInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assets: Option<String>,
+    // client、server、client_mappings等
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub downloads: BTreeMap<String, Download>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub java_version: Option<JavaVersion>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<Logging>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Version {
+    pub fn from_file(path: &Path) -> Result<Version> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    // 合并继承的版本：子版本的字段优先，参数接在后面，相同坐标的依赖库使用子版本的
+    pub fn merge(self, inherit: Version) -> Version {
+        let mut extra = inherit.extra;
+        extra.extend(self.extra);
+        let mut downloads = inherit.downloads;
+        downloads.extend(self.downloads);
+        let arguments = match (self.arguments, inherit.arguments) {
+            (Some(child), Some(mut parent)) => {
+                parent.jvm.extend(child.jvm);
+                parent.game.extend(child.game);
+                parent.extra.extend(child.extra);
+                Some(parent)
+            }
+            (child, parent) => child.or(parent),
+        };
+        // 子版本的依赖库在前面
+        let overridden: HashSet<String> = self.libraries.iter().map(|l| l.key()).collect();
+        let mut libraries = self.libraries;
+        libraries.extend(inherit.libraries.into_iter().filter(|l| !overridden.contains(&l.key())));
+        Version {
+            id: self.id,
+            inherits_from: inherit.inherits_from,
+            version_type: self.version_type.or(inherit.version_type),
+            main_class: self.main_class.or(inherit.main_class),
+            // 旧版Forge等会给出完整的参数
+            minecraft_arguments: self.minecraft_arguments.or(inherit.minecraft_arguments),
+            arguments,
+            libraries,
+            asset_index: self.asset_index.or(inherit.asset_index),
+            assets: self.assets.or(inherit.assets),
+            downloads,
+            java_version: self.java_version.or(inherit.java_version),
+            logging: self.logging.or(inherit.logging),
+            release_time: self.release_time.or(inherit.release_time),
+            time: self.time.or(inherit.time),
+            extra,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Arguments {
+    #[serde(default)]
+    pub game: Vec<Argument>,
+    #[serde(default)]
+    pub jvm: Vec<Argument>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// 参数：字符串或者带规则的参数
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Argument {
+    Plain(String),
+    Conditional(ConditionalArgument),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConditionalArgument {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    pub value: ArgumentValue,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ArgumentValue {
+    Single(String),
+    Many(Vec<String>),
+}
+
+impl ArgumentValue {
+    pub fn values(&self) -> &[String] {
+        match self {
+            ArgumentValue::Single(value) => std::slice::from_ref(value),
+            ArgumentValue::Many(values) => values,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Allow,
+    Disallow,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub action: RuleAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<OsRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<BTreeMap<String, bool>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OsRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // 正则表达式
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Library {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downloads: Option<LibraryDownloads>,
+    // 没有downloads时的Maven仓库
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Vec<Rule>>,
+    // 系统 => 分类名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub natives: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extract: Option<Extract>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Library {
+    // 合并时比较的坐标：group:artifact和分类，不包括版本
+    pub fn key(&self) -> String {
+        let name = self.name.split('@').next().unwrap_or_default();
+        let parts: Vec<&str> = name.split(':').collect();
+        match parts.as_slice() {
+            [group, artifact, _, classifier, ..] => format!("{}:{}:{}", group, artifact, classifier),
+            [group, artifact, ..] => format!("{}:{}", group, artifact),
+            _ => String::from(name),
+        }
+    }
+
+    pub fn artifact(&self) -> Option<&Download> {
+        self.downloads.as_ref()?.artifact.as_ref()
+    }
+
+    pub fn classifier(&self, name: &str) -> Option<&Download> {
+        self.downloads.as_ref()?.classifiers.get(name)
+    }
+
+    pub fn coordinate(&self) -> Result<MavenCoordinate> {
+        MavenCoordinate::parse(&self.name)
+    }
+
+    // 主文件的下载信息，没有downloads时从url指定的仓库（默认为官方仓库）下载
+    pub fn artifact_download(&self) -> Result<Option<Download>> {
+        if let Some(downloads) = &self.downloads {
+            return Ok(downloads.artifact.clone());
+        }
+        let coordinate = self.coordinate()?;
+        Ok(Some(Download {
+            path: Some(coordinate.path()),
+            url: coordinate.url(self.url.as_deref().unwrap_or(LIBRARIES)),
+            ..Download::default()
+        }))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LibraryDownloads {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<Download>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub classifiers: BTreeMap<String, Download>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Extract {
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// 可以下载的文件
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Download {
+    // 依赖库里的相对路径
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// 版本Json里的assetIndex
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetIndexInfo {
+    pub id: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_size: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// assets/indexes/<id>.json
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AssetIndex {
+    #[serde(default)]
+    pub objects: BTreeMap<String, AssetObject>,
+    // 1.6以前需要复制到resources目录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map_to_resources: Option<bool>,
+    #[serde(rename = "virtual", default, skip_serializing_if = "Option::is_none")]
+    pub is_virtual: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AssetObject {
+    pub hash: String,
+    pub size: u64,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JavaVersion {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub major_version: Option<u32>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Logging {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<LoggingConfig>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    // 例如-Dlog4j.configurationFile=${path}
+    pub argument: String,
+    pub file: LoggingFile,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub config_type: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LoggingFile {
+    pub id: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn round_trips_unknown_fields() {
+        let json = json!({
+            "id": "1.20.1",
+            "type": "release",
+            "mainClass": "net.minecraft.client.main.Main",
+            "complianceLevel": 1,
+            "minimumLauncherVersion": 21,
+            "arguments": {
+                "game": ["--username", "${auth_player_name}", {
+                    "rules": [{"action": "allow", "features": {"is_demo_user": true}}],
+                    "value": "--demo"
+                }],
+                "jvm": [{
+                    "rules": [{"action": "allow", "os": {"name": "osx", "future": "x"}}],
+                    "value": ["-XstartOnFirstThread"]
+                }],
+                "default-user-jvm": []
+            },
+            "assetIndex": {"id": "5", "sha1": "a", "size": 1, "totalSize": 2, "url": "https://example.com/5.json"},
+            "downloads": {"client": {"sha1": "b", "size": 3, "url": "https://example.com/client.jar"}},
+            "javaVersion": {"component": "java-runtime-gamma", "majorVersion": 17},
+            "libraries": [{
+                "name": "org.lwjgl:lwjgl:3.3.1:natives-linux",
+                "downloads": {"artifact": {"path": "a.jar", "sha1": "c", "size": 4, "url": "https://example.com/a.jar"}},
+                "rules": [{"action": "allow", "os": {"name": "linux"}}],
+                "checksums": ["d"]
+            }],
+            "logging": {"client": {
+                "argument": "-Dlog4j.configurationFile=${path}",
+                "file": {"id": "client-1.12.xml", "sha1": "e", "size": 5, "url": "https://example.com/client.xml"},
+                "type": "log4j2-xml"
+            }}
+        });
+        let version: Version = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(version.extra["complianceLevel"], 1);
+        assert_eq!(version.java_version.as_ref().unwrap().major_version, Some(17));
+        assert_eq!(version.libraries[0].extra["checksums"], json!(["d"]));
+        let arguments = version.arguments.as_ref().unwrap();
+        assert_eq!(arguments.game[0], Argument::Plain(String::from("--username")));
+        match &arguments.jvm[0] {
+            Argument::Conditional(arg) => assert_eq!(arg.value.values(), ["-XstartOnFirstThread"]),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(serde_json::to_value(&version).unwrap(), json);
+    }
+
+    #[test]
+    fn parses_legacy_versions() {
+        let version: Version = serde_json::from_value(json!({
+            "id": "1.8.9",
+            "minecraftArguments": "--username ${auth_player_name} --version ${version_name}",
+            "assets": "1.8",
+            "libraries": [{
+                "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209",
+                "downloads": {"classifiers": {"natives-linux": {"path": "n.jar", "url": "https://example.com/n.jar"}}},
+                "extract": {"exclude": ["META-INF/"]},
+                "natives": {"linux": "natives-linux"}
+            }, {
+                "name": "net.minecraftforge:forge:1.8.9-11.15.1.2318",
+                "url": "https://maven.minecraftforge.net/"
+            }]
+        })).unwrap();
+        assert!(version.arguments.is_none());
+        assert_eq!(version.libraries[0].classifier("natives-linux").unwrap().path.as_deref(), Some("n.jar"));
+        assert_eq!(version.libraries[0].extract.as_ref().unwrap().exclude, vec!["META-INF/"]);
+        assert!(version.libraries[1].artifact().is_none());
+        // 没有downloads的依赖库从url下载
+        let forge = version.libraries[1].artifact_download().unwrap().unwrap();
+        assert_eq!(forge.path.as_deref(), Some("net/minecraftforge/forge/1.8.9-11.15.1.2318/forge-1.8.9-11.15.1.2318.jar"));
+        assert_eq!(forge.url, "https://maven.minecraftforge.net/net/minecraftforge/forge/1.8.9-11.15.1.2318/forge-1.8.9-11.15.1.2318.jar");
+        assert!(version.libraries[0].artifact_download().unwrap().is_none());
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+use std::fs::{create_dir_all, remove_dir_all, File};
+use std::io;
+use std::path::Path;
+use zip::ZipArchive;
+use crate::error::missing;
+use crate::java::normalize_arch;
+use crate::model::{Download, Library};
+use crate::rule::{is_allowed, Features, Platform};
+use crate::Result;
+
+// Native库的两种格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NativeKind {
+    // 1.19以前：downloads.classifiers + natives
+    Classifier,
+    // 1.19以后：名字以:natives-xxx结尾的单独依赖
+    Artifact,
+}
+
+// 一个需要解压到natives目录的库
+#[derive(Clone, Debug, PartialEq)]
+pub struct Native {
+    pub kind: NativeKind,
+    pub download: Download,
+    // 解压时跳过的路径前缀
+    pub exclude: Vec<String>,
+}
+
+impl Native {
+    // 在依赖库目录里的相对路径
+    pub fn path(&self) -> Result<&str> {
+        self.download.path.as_deref().ok_or_else(|| missing("downloads.path"))
+    }
+}
+
+// natives-macos-arm64 => (macos, Some(aarch64))
+fn split_classifier(classifier: &str) -> Option<(&str, Option<String>)> {
+    let rest = classifier.strip_prefix("natives-")?;
+    Some(match rest.split_once('-') {
+        Some((os, arch)) => (os, Some(normalize_arch(&arch.replace("aarch_64", "aarch64")))),
+        None => (rest, None),
+    })
+}
+
+// 旧格式：natives里的分类名，${arch}是32或64
+fn legacy_native(library: &Library, platform: &Platform) -> Option<Native> {
+    let classifier = library.natives.as_ref()?.get(&platform.os)?;
+    let bits = if platform.arch == "x86" { "32" } else { "64" };
+    let classifier = classifier.replace("${arch}", bits);
+    // 有些第三方Json为arm64单独提供了分类
+    let arm64 = library.classifier(&format!("{}-arm64", classifier)).filter(|_| platform.is_arm64());
+    let download = arm64.or_else(|| library.classifier(&classifier))?;
+    let exclude = library.extract.as_ref().map(|e| e.exclude.clone()).unwrap_or_default();
+    Some(Native { kind: NativeKind::Classifier, download: download.clone(), exclude })
+}
+
+// 当前平台需要的所有Native库
+pub fn resolve(libraries: &[Library], platform: &Platform) -> Vec<Native> {
+    let mut out = Vec::new();
+    // 新格式：(去掉分类的名字, 架构, Native)
+    let mut artifacts = Vec::new();
+    for library in libraries {
+        if !is_allowed(library.rules.as_deref(), platform, &Features::default()) {
+            continue;
+        }
+        if library.natives.is_some() {
+            out.extend(legacy_native(library, platform));
+            continue;
+        }
+        let parts: Vec<&str> = library.name.split(':').collect();
+        let Some((_, arch)) = parts.get(3).and_then(|c| split_classifier(c)) else { continue };
+        let Some(download) = library.artifact() else { continue };
+        let exclude = library.extract.as_ref().map(|e| e.exclude.clone()).unwrap_or_default();
+        let native = Native { kind: NativeKind::Artifact, download: download.clone(), exclude };
+        artifacts.push((parts[..3].join(":"), arch, native));
+    }
+    // 有当前架构的版本时只用它，否则用不带架构的版本
+    let exact: HashSet<String> = artifacts.iter()
+        .filter(|(_, arch, _)| arch.as_deref() == Some(platform.arch.as_str()))
+        .map(|(name, _, _)| name.clone())
+        .collect();
+    for (name, arch, native) in artifacts {
+        let selected = match arch {
+            Some(arch) => arch == platform.arch,
+            None => !exact.contains(&name),
+        };
+        if selected {
+            out.push(native);
+        }
+    }
+    out
+}
+
+fn is_native_file(name: &str) -> bool {
+    [".so", ".dll", ".dylib", ".jnilib"].iter().any(|ext| name.ends_with(ext))
+}
+
+// 解压一个Native库，始终跳过META-INF
+pub fn extract(file: &Path, dir: &Path, native: &Native) -> Result<()> {
+    create_dir_all(dir)?;
+    let mut zip_archive = ZipArchive::new(File::open(file)?)?;
+    for i in 0..zip_archive.len() {
+        let mut entry = zip_archive.by_index(i)?;
+        if !entry.is_file() {
+            continue;
+        }
+        let name = String::from(entry.name());
+        if name.starts_with("META-INF/") || native.exclude.iter().any(|e| name.starts_with(e.as_str())) {
+            continue;
+        }
+        // 不解压到目录外面
+        let Some(relative) = entry.enclosed_name().map(Path::to_path_buf) else { continue };
+        let path = match native.kind {
+            NativeKind::Classifier => dir.join(relative),
+            // 新格式的库按架构分目录存放，只取出库文件放在根目录
+            NativeKind::Artifact => {
+                if !is_native_file(&name) {
+                    continue;
+                }
+                match relative.file_name() {
+                    Some(file_name) => dir.join(file_name),
+                    None => continue,
+                }
+            }
+        };
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(path)?)?;
+    }
+    Ok(())
+}
+
+// 清空natives目录后从依赖库目录重新解压
+pub fn extract_all(natives: &[Native], library_dir: &Path, dir: &Path) -> Result<()> {
+    if dir.exists() {
+        remove_dir_all(dir)?;
+    }
+    create_dir_all(dir)?;
+    for native in natives {
+        extract(&library_dir.join(native.path()?), dir, native)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use serde_json::json;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("akiraka-natives-{}-{}", name, std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn library(json: serde_json::Value) -> Library {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn artifact(name: &str, path: &str, os: &str) -> Library {
+        library(json!({
+            "name": name,
+            "downloads": {"artifact": {"path": path, "url": "", "sha1": "", "size": 0}},
+            "rules": [{"action": "allow", "os": {"name": os}}]
+        }))
+    }
+
+    fn paths(natives: &[Native]) -> Vec<&str> {
+        natives.iter().map(|n| n.path().unwrap()).collect()
+    }
+
+    #[test]
+    fn resolves_both_schemes() {
+        let libraries = vec![
+            library(json!({"name": "org.lwjgl:lwjgl:3.3.1", "downloads": {"artifact": {"path": "lwjgl.jar"}}})),
+            artifact("org.lwjgl:lwjgl:3.3.1:natives-linux", "linux.jar", "linux"),
+            artifact("org.lwjgl:lwjgl:3.3.1:natives-macos", "macos.jar", "osx"),
+            artifact("org.lwjgl:lwjgl:3.3.1:natives-macos-arm64", "macos-arm64.jar", "osx"),
+            artifact("org.lwjgl:lwjgl:3.3.1:natives-windows", "windows.jar", "windows"),
+            artifact("org.lwjgl:lwjgl:3.3.1:natives-windows-x86", "windows-x86.jar", "windows"),
+            library(json!({
+                "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209",
+                "downloads": {"classifiers": {
+                    "natives-linux": {"path": "legacy-linux.jar"},
+                    "natives-windows-32": {"path": "legacy-windows-32.jar"},
+                    "natives-windows-64": {"path": "legacy-windows-64.jar"}
+                }},
+                "extract": {"exclude": ["META-INF/", "readme.txt"]},
+                "natives": {"linux": "natives-linux", "windows": "natives-windows-${arch}"}
+            })),
+        ];
+        let linux = resolve(&libraries, &Platform::new("linux", "6.1", "amd64"));
+        assert_eq!(paths(&linux), vec!["legacy-linux.jar", "linux.jar"]);
+        assert_eq!(linux[0].exclude, vec!["META-INF/", "readme.txt"]);
+        // Linux arm64没有单独的版本，使用默认的
+        assert_eq!(paths(&resolve(&libraries, &Platform::new("linux", "6.1", "aarch64"))), vec!["legacy-linux.jar", "linux.jar"]);
+        assert_eq!(paths(&resolve(&libraries, &Platform::new("osx", "13.4", "aarch64"))), vec!["macos-arm64.jar"]);
+        assert_eq!(paths(&resolve(&libraries, &Platform::new("osx", "12.0", "x86_64"))), vec!["macos.jar"]);
+        assert_eq!(paths(&resolve(&libraries, &Platform::new("windows", "10.0", "x86"))), vec!["legacy-windows-32.jar", "windows-x86.jar"]);
+        assert_eq!(paths(&resolve(&libraries, &Platform::new("windows", "10.0", "amd64"))), vec!["legacy-windows-64.jar", "windows.jar"]);
+
+        // 新格式的库也使用extract.exclude
+        let excluded = library(json!({
+            "name": "org.lwjgl:lwjgl-glfw:3.3.1:natives-linux",
+            "downloads": {"artifact": {"path": "glfw-linux.jar"}},
+            "extract": {"exclude": ["META-INF/"]}
+        }));
+        assert_eq!(resolve(&[excluded], &Platform::new("linux", "6.1", "amd64"))[0].exclude, vec!["META-INF/"]);
+    }
+
+    #[test]
+    fn extracts_with_excludes() {
+        let dir = temp_dir("extract");
+        let library_dir = dir.join("libraries");
+        create_dir_all(&library_dir).unwrap();
+        let mut zip = ZipWriter::new(File::create(library_dir.join("legacy.jar")).unwrap());
+        for name in ["META-INF/MANIFEST.MF", "liblwjgl.so", "readme.txt"] {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        let mut zip = ZipWriter::new(File::create(library_dir.join("modern.jar")).unwrap());
+        for name in ["META-INF/MANIFEST.MF", "linux/x64/org/lwjgl/glfw/libglfw.so", "linux/x64/org/lwjgl/glfw/libglfw.so.sha1"] {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let natives = vec![
+            Native { kind: NativeKind::Classifier, download: Download { path: Some(String::from("legacy.jar")), ..Download::default() }, exclude: vec![String::from("readme.txt")] },
+            Native { kind: NativeKind::Artifact, download: Download { path: Some(String::from("modern.jar")), ..Download::default() }, exclude: Vec::new() },
+        ];
+        let natives_dir = dir.join("natives");
+        create_dir_all(&natives_dir).unwrap();
+        File::create(natives_dir.join("stale.so")).unwrap();
+        extract_all(&natives, &library_dir, &natives_dir).unwrap();
+
+        let mut files: Vec<String> = std::fs::read_dir(&natives_dir).unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["libglfw.so", "liblwjgl.so"]);
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,26 +1,28 @@
-use reqwest;
-use serde_json::Value;
-use crate::core::VersionSource;
+use reqwest::blocking::Client;
+use crate::model::VersionManifest;
+use crate::source::{DownloadSources, PISTON_META};
+use crate::{Result, VersionSource};
 
-const URL_VERSION_MANIFEST: &str = "https://piston-meta.mojang.com/mc/game/version_manifest.json";
+const URL_VERSION_MANIFEST: &str = "/mc/game/version_manifest.json";
 
-pub fn get_version_sources(snapshot: bool, release: bool, old_beta: bool, old_alpha: bool) -> Result<Vec<VersionSource>, std::io::Error> {
+pub fn get_version_sources(sources: &DownloadSources, snapshot: bool, release: bool, old_beta: bool, old_alpha: bool) -> Result<Vec<VersionSource>> {
     let mut result = Vec::new();
-    let get: Value = serde_json::from_str(&*reqwest::blocking::get(URL_VERSION_MANIFEST).unwrap().text().unwrap()).unwrap();
-    for i in get["versions"].as_array().unwrap() {
-        let version_type = i["type"].as_str().unwrap();
+    let url = format!("{}{}", PISTON_META, URL_VERSION_MANIFEST);
+    let manifest: VersionManifest = serde_json::from_str(&sources.get_text(&Client::new(), &url)?)?;
+    for i in manifest.versions {
+        let version_type = i.version_type.as_str();
         if version_type == "snapshot" && snapshot ||
             version_type == "release" && release ||
             version_type == "old_beta" && old_beta ||
             version_type == "old_alpha" && old_alpha {
             result.push(VersionSource {
-                version_id: i["id"].as_str().unwrap().parse().unwrap(),
-                version_url: i["url"].as_str().unwrap().parse().unwrap(),
-                version_type: i["type"].as_str().unwrap().parse().unwrap(),
-                release_time: i["releaseTime"].as_str().unwrap().parse().unwrap(),
+                version_id: i.id,
+                version_url: i.url,
+                version_type: i.version_type,
+                release_time: i.release_time,
             });
         }
     }
 
     Ok(result)
-}
\ No newline at end of file
+}
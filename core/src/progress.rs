@@ -0,0 +1,256 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use crate::download::DownloadTask;
+use crate::{CoreError, Result};
+
+// 安装的阶段，按进行的先后排序
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstallPhase {
+    Json,
+    Client,
+    Libraries,
+    // 运行Forge安装器的处理器
+    Processors,
+    Natives,
+    AssetIndex,
+    Assets,
+    Done,
+}
+
+// 进度快照
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: InstallPhase,
+    pub total_files: usize,
+    pub completed_files: usize,
+    pub failed_files: usize,
+    pub total_bytes: u64,
+    pub completed_bytes: u64,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress {
+            phase: InstallPhase::Json,
+            total_files: 0,
+            completed_files: 0,
+            failed_files: 0,
+            total_bytes: 0,
+            completed_bytes: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ProgressEvent {
+    // 进入新的阶段
+    Phase(InstallPhase),
+    // 文件数量或字节数有变化
+    Update(Progress),
+    // 单个文件下载失败
+    Failed {
+        url: String,
+        path: PathBuf,
+        error: String,
+    },
+}
+
+// 进度接收者，可以是回调也可以是通道
+pub trait ProgressSink: Send + Sync {
+    fn report(&self, event: ProgressEvent);
+}
+
+impl<F: Fn(ProgressEvent) + Send + Sync> ProgressSink for F {
+    fn report(&self, event: ProgressEvent) {
+        self(event)
+    }
+}
+
+impl ProgressSink for Sender<ProgressEvent> {
+    fn report(&self, event: ProgressEvent) {
+        // 接收端已经关闭时忽略
+        let _ = self.send(event);
+    }
+}
+
+// 不需要进度时使用
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn report(&self, _event: ProgressEvent) {}
+}
+
+// 取消令牌，克隆后共享同一个状态
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    // 已取消时返回错误，方便用?提前退出
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(CoreError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// 安装过程中汇总进度并转发给接收者
+pub struct Tracker {
+    sink: Arc<dyn ProgressSink>,
+    state: Mutex<Progress>,
+}
+
+impl Tracker {
+    pub fn new(sink: Arc<dyn ProgressSink>) -> Tracker {
+        Tracker {
+            sink,
+            state: Mutex::new(Progress::default()),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Progress)) {
+        let snapshot = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut state);
+            state.clone()
+        };
+        self.sink.report(ProgressEvent::Update(snapshot));
+    }
+
+    pub fn phase(&self, phase: InstallPhase) {
+        self.sink.report(ProgressEvent::Phase(phase));
+        self.update(|p| p.phase = phase);
+    }
+
+    pub fn add(&self, task: &DownloadTask) {
+        self.update(|p| {
+            p.total_files += 1;
+            p.total_bytes += task.size.unwrap_or(0);
+        });
+    }
+
+    pub fn complete(&self, task: &DownloadTask) {
+        let bytes = task.size
+            .or_else(|| task.path.metadata().ok().map(|m| m.len()))
+            .unwrap_or(0);
+        self.update(|p| {
+            p.completed_files += 1;
+            p.completed_bytes += bytes;
+        });
+    }
+
+    pub fn fail(&self, task: &DownloadTask, error: &CoreError) {
+        self.sink.report(ProgressEvent::Failed {
+            url: task.url.clone(),
+            path: task.path.clone(),
+            error: error.to_string(),
+        });
+        self.update(|p| p.failed_files += 1);
+    }
+
+    pub fn snapshot(&self) -> Progress {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn task(size: Option<u64>) -> DownloadTask {
+        DownloadTask::new("https://example.com/a.jar", "/nonexistent/a.jar").with_size(size)
+    }
+
+    #[test]
+    fn phases_are_ordered() {
+        let phases = [
+            InstallPhase::Json,
+            InstallPhase::Client,
+            InstallPhase::Libraries,
+            InstallPhase::Processors,
+            InstallPhase::Natives,
+            InstallPhase::AssetIndex,
+            InstallPhase::Assets,
+            InstallPhase::Done,
+        ];
+        assert!(phases.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn tracker_reports_to_closure() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let e = events.clone();
+        let tracker = Tracker::new(Arc::new(move |event| e.lock().unwrap().push(event)));
+        tracker.phase(InstallPhase::Libraries);
+        tracker.add(&task(Some(10)));
+        tracker.add(&task(Some(5)));
+        // 没有大小时按文件实际大小计算，文件不存在时为0
+        tracker.add(&task(None));
+        tracker.complete(&task(Some(10)));
+        tracker.complete(&task(None));
+        assert_eq!(tracker.snapshot(), Progress {
+            phase: InstallPhase::Libraries,
+            total_files: 3,
+            completed_files: 2,
+            failed_files: 0,
+            total_bytes: 15,
+            completed_bytes: 10,
+        });
+        let events = events.lock().unwrap();
+        assert!(matches!(events[0], ProgressEvent::Phase(InstallPhase::Libraries)));
+        // 每次变化后都有一个快照
+        assert_eq!(events.iter().filter(|e| matches!(e, ProgressEvent::Update(_))).count(), 6);
+        match events.last().unwrap() {
+            ProgressEvent::Update(progress) => assert_eq!(progress, &tracker.snapshot()),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn tracker_reports_failures_to_channel() {
+        let (sender, receiver) = channel();
+        let tracker = Tracker::new(Arc::new(sender));
+        tracker.add(&task(Some(1)));
+        tracker.fail(&task(Some(1)), &CoreError::Checksum(String::from("a.jar")));
+        assert_eq!(tracker.snapshot().failed_files, 1);
+        assert_eq!(tracker.snapshot().completed_files, 0);
+        let events: Vec<ProgressEvent> = receiver.try_iter().collect();
+        let failure = events.iter().find_map(|e| match e {
+            ProgressEvent::Failed { url, path, error } => Some((url, path, error)),
+            _ => None,
+        }).unwrap();
+        assert_eq!(failure.0, "https://example.com/a.jar");
+        assert_eq!(failure.1, &PathBuf::from("/nonexistent/a.jar"));
+        assert_eq!(failure.2, &CoreError::Checksum(String::from("a.jar")).to_string());
+        // 接收端关闭后不会panic
+        drop(receiver);
+        tracker.phase(InstallPhase::Done);
+        assert_eq!(tracker.snapshot().phase, InstallPhase::Done);
+    }
+
+    #[test]
+    fn cancel_token_is_shared() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(CoreError::Cancelled)));
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs::read_dir;
+use std::path::Path;
+use crate::model::Version;
+use crate::{CoreError, Result};
+
+// 合并了整条inheritsFrom链的版本
+#[derive(Clone, Debug)]
+pub struct ResolvedVersion {
+    pub version: Version,
+    // 继承链上的版本目录名，从自己开始
+    pub chain: Vec<String>,
+}
+
+impl ResolvedVersion {
+    // 游戏主文件所在的版本目录：jar字段指定的或者继承链的最底层
+    pub fn jar(&self) -> &str {
+        match self.version.extra.get("jar").and_then(|j| j.as_str()) {
+            Some(jar) => jar,
+            None => self.chain.last().map(String::as_str).unwrap_or(&self.version.id),
+        }
+    }
+
+    pub fn has_inherit(&self) -> bool {
+        self.chain.len() > 1
+    }
+}
+
+// 按ID查找已安装的版本，目录名与ID不同时扫描整个versions目录
+fn find(versions_dir: &Path, id: &str) -> Result<Option<(String, Version)>> {
+    let path = versions_dir.join(id).join(format!("{}.json", id));
+    if path.is_file() {
+        return Ok(Some((String::from(id), Version::from_file(&path)?)));
+    }
+    if !versions_dir.is_dir() {
+        return Ok(None);
+    }
+    for i in read_dir(versions_dir)? {
+        let name = i?.file_name();
+        let Some(name) = name.to_str() else {
+            continue
+        };
+        let path = versions_dir.join(name).join(format!("{}.json", name));
+        // 损坏的版本直接跳过
+        let Ok(version) = Version::from_file(&path) else {
+            continue
+        };
+        if version.id == id {
+            return Ok(Some((String::from(name), version)));
+        }
+    }
+    Ok(None)
+}
+
+// 读取版本并按inheritsFrom逐层合并
+pub fn resolve_version(versions_dir: &Path, name: &str) -> Result<ResolvedVersion> {
+    let path = versions_dir.join(name).join(format!("{}.json", name));
+    if !path.is_file() {
+        return Err(CoreError::MissingVersion(String::from(name)));
+    }
+    let mut version = Version::from_file(&path)?;
+    let mut chain = vec![String::from(name)];
+    let mut ids = vec![version.id.clone()];
+    while let Some(inherits_from) = version.inherits_from.clone() {
+        if ids.contains(&inherits_from) {
+            ids.push(inherits_from);
+            return Err(CoreError::InheritsFromCycle(ids));
+        }
+        let (dir, parent) = find(versions_dir, &inherits_from)?
+            .ok_or(CoreError::MissingInheritsFrom(inherits_from))?;
+        chain.push(dir);
+        ids.push(parent.id.clone());
+        version = version.merge(parent);
+    }
+    Ok(ResolvedVersion { version, chain })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::model::Argument;
+
+    fn versions_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/versions")
+    }
+
+    fn library_names(version: &Version) -> Vec<&str> {
+        version.libraries.iter().map(|l| l.name.as_str()).collect()
+    }
+
+    #[test]
+    fn resolves_fabric() {
+        let resolved = resolve_version(&versions_dir(), "fabric-loader-0.14.21-1.20.1").unwrap();
+        let version = &resolved.version;
+        assert_eq!(resolved.chain, vec!["fabric-loader-0.14.21-1.20.1", "1.20.1"]);
+        assert_eq!(resolved.jar(), "1.20.1");
+        assert_eq!(version.id, "fabric-loader-0.14.21-1.20.1");
+        assert_eq!(version.inherits_from, None);
+        assert_eq!(version.main_class.as_deref(), Some("net.fabricmc.loader.impl.launch.knot.KnotClient"));
+        // 原版的字段
+        assert_eq!(version.asset_index.as_ref().unwrap().id, "5");
+        assert_eq!(version.java_version.as_ref().unwrap().major_version, Some(17));
+        assert!(version.logging.as_ref().unwrap().client.is_some());
+        assert!(version.downloads.contains_key("client"));
+        // Fabric的asm替换原版的
+        assert_eq!(library_names(version), vec![
+            "net.fabricmc:tiny-mappings-parser:0.3.0+build.17",
+            "org.ow2.asm:asm:9.5",
+            "net.fabricmc:intermediary:1.20.1",
+            "net.fabricmc:fabric-loader:0.14.21",
+            "com.mojang:logging:1.1.1",
+            "org.lwjgl:lwjgl:3.3.1",
+            "org.lwjgl:lwjgl:3.3.1:natives-linux",
+        ]);
+        let arguments = version.arguments.as_ref().unwrap();
+        assert_eq!(arguments.jvm.len(), 4);
+        assert_eq!(arguments.jvm[3], Argument::Plain(String::from("-DFabricMcEmu= net.minecraft.client.main.Main ")));
+        assert_eq!(arguments.game.len(), 4);
+    }
+
+    #[test]
+    fn resolves_forge_and_optifine_chain() {
+        let resolved = resolve_version(&versions_dir(), "1.12.2-OptiFine_HD_U_G5").unwrap();
+        let version = &resolved.version;
+        assert_eq!(resolved.chain, vec!["1.12.2-OptiFine_HD_U_G5", "1.12.2-forge-14.23.5.2860", "1.12.2"]);
+        assert_eq!(resolved.jar(), "1.12.2");
+        assert_eq!(version.main_class.as_deref(), Some("net.minecraft.launchwrapper.Launch"));
+        assert!(version.minecraft_arguments.as_ref().unwrap().ends_with("--tweakClass optifine.OptiFineForgeTweaker"));
+        assert_eq!(version.assets.as_deref(), Some("1.12"));
+        assert_eq!(library_names(version), vec![
+            "optifine:OptiFine:1.12.2_HD_U_G5",
+            "net.minecraft:launchwrapper:1.12",
+            "net.minecraftforge:forge:1.12.2-14.23.5.2860",
+            "org.apache.logging.log4j:log4j-api:2.15.0",
+            "org.apache.logging.log4j:log4j-core:2.15.0",
+            "net.sf.jopt-simple:jopt-simple:5.0.3",
+        ]);
+    }
+
+    #[test]
+    fn reports_cycles_and_missing_parents() {
+        match resolve_version(&versions_dir(), "cycle-a") {
+            Err(CoreError::InheritsFromCycle(chain)) => assert_eq!(chain, vec!["cycle-a", "cycle-b", "cycle-a"]),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(resolve_version(&versions_dir(), "orphan"), Err(CoreError::MissingInheritsFrom(id)) if id == "1.7.10"));
+        assert!(matches!(resolve_version(&versions_dir(), "1.7.10"), Err(CoreError::MissingVersion(_))));
+        let vanilla = resolve_version(&versions_dir(), "1.20.1").unwrap();
+        assert!(!vanilla.has_inherit());
+        assert_eq!(vanilla.jar(), "1.20.1");
+    }
+}
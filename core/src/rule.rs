@@ -0,0 +1,383 @@
+use std::env::consts::{ARCH, OS};
+use std::sync::OnceLock;
+use regex::Regex;
+use sysinfo::{System, SystemExt};
+use crate::java::normalize_arch;
+use crate::model::{Argument, Rule, RuleAction};
+
+// 规则检查用的平台信息，名字与版本Json里的相同
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Platform {
+    // windows、osx或linux
+    pub os: String,
+    // 与爪哇的os.version相同
+    pub version: String,
+    // 与爪哇的os.arch相同（x86、amd64、aarch64）
+    pub arch: String,
+}
+
+impl Platform {
+    pub fn new(os: &str, version: &str, arch: &str) -> Platform {
+        Platform {
+            os: String::from(os),
+            version: String::from(version),
+            arch: normalize_arch(arch),
+        }
+    }
+
+    // 当前的平台
+    pub fn current() -> &'static Platform {
+        static CURRENT: OnceLock<Platform> = OnceLock::new();
+        CURRENT.get_or_init(|| {
+            let os = if OS == "macos" { "osx" } else { OS };
+            Platform::new(os, &os_version(), ARCH)
+        })
+    }
+
+    pub fn is_arm64(&self) -> bool {
+        self.arch == "aarch64"
+    }
+}
+
+// 与爪哇的os.version相同格式的系统版本
+fn os_version() -> String {
+    let system = System::new();
+    if OS == "windows" {
+        // 爪哇在Windows 10和11上都返回10.0
+        match system.os_version().unwrap_or_default().split(' ').next().unwrap_or_default() {
+            "7" => String::from("6.1"),
+            "8" => String::from("6.2"),
+            "8.1" => String::from("6.3"),
+            _ => String::from("10.0"),
+        }
+    } else if OS == "macos" {
+        system.os_version().unwrap_or_default()
+    } else {
+        system.kernel_version().unwrap_or_default()
+    }
+}
+
+// 规则里的features，默认全部为false
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Features {
+    pub is_demo_user: bool,
+    pub has_custom_resolution: bool,
+    pub has_quick_plays_support: bool,
+    pub is_quick_play_singleplayer: bool,
+    pub is_quick_play_multiplayer: bool,
+    pub is_quick_play_realms: bool,
+}
+
+impl Features {
+    pub fn get(&self, name: &str) -> bool {
+        match name {
+            "is_demo_user" => self.is_demo_user,
+            "has_custom_resolution" => self.has_custom_resolution,
+            "has_quick_plays_support" => self.has_quick_plays_support,
+            "is_quick_play_singleplayer" => self.is_quick_play_singleplayer,
+            "is_quick_play_multiplayer" => self.is_quick_play_multiplayer,
+            "is_quick_play_realms" => self.is_quick_play_realms,
+            // 不认识的功能当作没有
+            _ => false,
+        }
+    }
+}
+
+// 一条规则是否适用于这个平台
+fn rule_matches(rule: &Rule, platform: &Platform, features: &Features) -> bool {
+    if let Some(os) = &rule.os {
+        if let Some(name) = &os.name {
+            if *name != platform.os {
+                return false;
+            }
+        }
+        if let Some(arch) = &os.arch {
+            if normalize_arch(arch) != platform.arch {
+                return false;
+            }
+        }
+        // 系统版本是正则表达式
+        if let Some(version) = &os.version {
+            match Regex::new(version) {
+                Ok(regex) if regex.is_match(&platform.version) => {}
+                _ => return false,
+            }
+        }
+    }
+    if let Some(required) = &rule.features {
+        for (name, value) in required {
+            if *value != features.get(name) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// 规则检查函数：默认不允许，最后一条匹配的规则决定结果
+pub fn check_rule(rules: &[Rule], platform: &Platform, features: &Features) -> bool {
+    let mut allow = false;
+    for rule in rules {
+        if rule_matches(rule, platform, features) {
+            allow = rule.action == RuleAction::Allow;
+        }
+    }
+    allow
+}
+
+// 没有规则时允许
+pub fn is_allowed(rules: Option<&[Rule]>, platform: &Platform, features: &Features) -> bool {
+    match rules {
+        Some(rules) => check_rule(rules, platform, features),
+        None => true,
+    }
+}
+
+// 参数在这个平台上的值，规则不允许时为空
+pub fn argument_values<'a>(argument: &'a Argument, platform: &Platform, features: &Features) -> &'a [String] {
+    match argument {
+        Argument::Plain(value) => std::slice::from_ref(value),
+        Argument::Conditional(argument) if check_rule(&argument.rules, platform, features) => argument.value.values(),
+        Argument::Conditional(_) => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::model::Version;
+
+    fn windows() -> Platform {
+        Platform::new("windows", "10.0", "amd64")
+    }
+
+    fn windows_x86() -> Platform {
+        Platform::new("windows", "6.1", "x86")
+    }
+
+    fn osx() -> Platform {
+        Platform::new("osx", "13.4", "aarch64")
+    }
+
+    fn linux() -> Platform {
+        Platform::new("linux", "6.1.0-13-amd64", "x86_64")
+    }
+
+    fn fixture(name: &str) -> Version {
+        Version::from_file(&std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/rules").join(format!("{}.json", name))).unwrap()
+    }
+
+    // 只看带规则的条目，换成完整的官方Json后测试表不用改
+    fn libraries(version: &Version, platform: &Platform) -> (Vec<String>, Vec<String>) {
+        let mut allowed = Vec::new();
+        let mut denied = Vec::new();
+        for library in version.libraries.iter().filter(|l| l.rules.is_some()) {
+            let target = if is_allowed(library.rules.as_deref(), platform, &Features::default()) { &mut allowed } else { &mut denied };
+            target.push(library.name.clone());
+        }
+        (allowed, denied)
+    }
+
+    fn arguments(version: &Version, game: bool, platform: &Platform, features: &Features) -> (Vec<String>, Vec<String>) {
+        let arguments = version.arguments.as_ref().unwrap();
+        let arguments = if game { &arguments.game } else { &arguments.jvm };
+        let mut allowed = Vec::new();
+        let mut denied = Vec::new();
+        for argument in arguments {
+            if let Argument::Conditional(argument) = argument {
+                let target = if check_rule(&argument.rules, platform, features) { &mut allowed } else { &mut denied };
+                target.extend(argument.value.values().iter().cloned());
+            }
+        }
+        (allowed, denied)
+    }
+
+    fn assert_split(actual: (Vec<String>, Vec<String>), allowed: &[&str], denied: &[&str], context: &str) {
+        for name in allowed {
+            assert!(actual.0.iter().any(|a| a == name), "{} should be allowed: {}", name, context);
+        }
+        for name in denied {
+            assert!(actual.1.iter().any(|a| a == name), "{} should be denied: {}", name, context);
+        }
+    }
+
+    #[test]
+    fn libraries_from_version_jsons() {
+        let table: Vec<(&str, Platform, Vec<&str>, Vec<&str>)> = vec![
+            ("1.8.9", windows(), vec![
+                "tv.twitch:twitch-platform:6.5",
+                "tv.twitch:twitch-external-platform:4.5",
+                "org.lwjgl.lwjgl:lwjgl:2.9.4-nightly-20150209",
+                "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209",
+            ], vec![
+                "org.lwjgl.lwjgl:lwjgl:2.9.2-nightly-20140822",
+                "org.lwjgl.lwjgl:lwjgl-platform:2.9.2-nightly-20140822",
+            ]),
+            ("1.8.9", osx(), vec![
+                "tv.twitch:twitch-platform:6.5",
+                "org.lwjgl.lwjgl:lwjgl:2.9.2-nightly-20140822",
+                "org.lwjgl.lwjgl:lwjgl-platform:2.9.2-nightly-20140822",
+            ], vec![
+                "tv.twitch:twitch-external-platform:4.5",
+                "org.lwjgl.lwjgl:lwjgl:2.9.4-nightly-20150209",
+                "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209",
+            ]),
+            ("1.8.9", linux(), vec![
+                "org.lwjgl.lwjgl:lwjgl:2.9.4-nightly-20150209",
+                "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209",
+            ], vec![
+                "tv.twitch:twitch-platform:6.5",
+                "tv.twitch:twitch-external-platform:4.5",
+                "org.lwjgl.lwjgl:lwjgl:2.9.2-nightly-20140822",
+            ]),
+            ("1.12.2", osx(), vec![
+                "org.lwjgl.lwjgl:lwjgl:2.9.2-nightly-20140822",
+                "org.lwjgl.lwjgl:lwjgl_util:2.9.2-nightly-20140822",
+            ], vec![
+                "org.lwjgl.lwjgl:lwjgl:2.9.4-nightly-20150209",
+                "org.lwjgl.lwjgl:lwjgl_util:2.9.4-nightly-20150209",
+            ]),
+            ("1.12.2", linux(), vec![
+                "org.lwjgl.lwjgl:lwjgl:2.9.4-nightly-20150209",
+                "org.lwjgl.lwjgl:lwjgl_util:2.9.4-nightly-20150209",
+            ], vec![
+                "org.lwjgl.lwjgl:lwjgl:2.9.2-nightly-20140822",
+                "org.lwjgl.lwjgl:lwjgl_util:2.9.2-nightly-20140822",
+            ]),
+            ("1.16.5", windows(), vec![
+                "org.lwjgl:lwjgl:3.2.2",
+                "org.lwjgl:lwjgl-glfw:3.2.2",
+            ], vec![
+                "org.lwjgl:lwjgl:3.2.1",
+                "org.lwjgl:lwjgl-glfw:3.2.1",
+            ]),
+            ("1.16.5", osx(), vec![
+                "org.lwjgl:lwjgl:3.2.1",
+                "org.lwjgl:lwjgl-glfw:3.2.1",
+            ], vec![
+                "org.lwjgl:lwjgl:3.2.2",
+                "org.lwjgl:lwjgl-glfw:3.2.2",
+            ]),
+            ("1.19.4", linux(), vec![
+                "org.lwjgl:lwjgl:3.3.1:natives-linux",
+                "com.mojang:text2speech:1.13.9:natives-linux",
+            ], vec![
+                "org.lwjgl:lwjgl:3.3.1:natives-macos",
+                "org.lwjgl:lwjgl:3.3.1:natives-windows",
+                "com.mojang:text2speech:1.13.9:natives-windows",
+            ]),
+            ("1.19.4", osx(), vec![
+                "org.lwjgl:lwjgl:3.3.1:natives-macos",
+                "org.lwjgl:lwjgl:3.3.1:natives-macos-arm64",
+            ], vec![
+                "org.lwjgl:lwjgl:3.3.1:natives-linux",
+                "org.lwjgl:lwjgl:3.3.1:natives-windows",
+            ]),
+            ("1.19.4", windows(), vec![
+                "org.lwjgl:lwjgl:3.3.1:natives-windows",
+                "org.lwjgl:lwjgl:3.3.1:natives-windows-arm64",
+                "org.lwjgl:lwjgl:3.3.1:natives-windows-x86",
+                "com.mojang:text2speech:1.13.9:natives-windows",
+            ], vec![
+                "org.lwjgl:lwjgl:3.3.1:natives-linux",
+                "org.lwjgl:lwjgl:3.3.1:natives-macos",
+            ]),
+            ("1.20.1", linux(), vec![
+                "org.lwjgl:lwjgl:3.3.1:natives-linux",
+            ], vec![
+                "org.lwjgl:lwjgl:3.3.1:natives-macos",
+                "org.lwjgl:lwjgl:3.3.1:natives-windows",
+            ]),
+        ];
+        for (name, platform, allowed, denied) in table {
+            let context = format!("{} on {:?}", name, platform);
+            assert_split(libraries(&fixture(name), &platform), &allowed, &denied, &context);
+        }
+    }
+
+    #[test]
+    fn jvm_arguments_from_version_jsons() {
+        let table: Vec<(&str, Platform, Vec<&str>, Vec<&str>)> = vec![
+            ("1.16.5", windows(), vec![
+                "-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump",
+                "-Dos.name=Windows 10",
+                "-Dos.version=10.0",
+            ], vec!["-XstartOnFirstThread", "-Xss1M"]),
+            ("1.16.5", windows_x86(), vec![
+                "-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump",
+                "-Xss1M",
+            ], vec!["-XstartOnFirstThread", "-Dos.name=Windows 10"]),
+            ("1.20.1", osx(), vec![
+                "-XstartOnFirstThread",
+            ], vec![
+                "-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump",
+                "-Xss1M",
+            ]),
+            ("1.20.1", linux(), vec![], vec![
+                "-XstartOnFirstThread",
+                "-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump",
+                "-Xss1M",
+            ]),
+        ];
+        for (name, platform, allowed, denied) in table {
+            let context = format!("{} on {:?}", name, platform);
+            assert_split(arguments(&fixture(name), false, &platform, &Features::default()), &allowed, &denied, &context);
+        }
+    }
+
+    #[test]
+    fn game_arguments_with_features() {
+        let json = fixture("1.20.1");
+        let conditional = [
+            "--demo",
+            "--width", "${resolution_width}", "--height", "${resolution_height}",
+            "--quickPlayPath", "${quickPlayPath}",
+            "--quickPlaySingleplayer", "${quickPlaySingleplayer}",
+            "--quickPlayMultiplayer", "${quickPlayMultiplayer}",
+            "--quickPlayRealms", "${quickPlayRealms}",
+        ];
+        assert!(arguments(&json, true, &linux(), &Features::default()).0.is_empty());
+        assert_split(arguments(&json, true, &linux(), &Features::default()), &[], &conditional, "default features");
+
+        let features = Features {
+            is_demo_user: true,
+            has_custom_resolution: true,
+            has_quick_plays_support: true,
+            is_quick_play_multiplayer: true,
+            ..Features::default()
+        };
+        assert_split(arguments(&json, true, &linux(), &features), &[
+            "--demo",
+            "--width", "${resolution_width}", "--height", "${resolution_height}",
+            "--quickPlayPath", "${quickPlayPath}",
+            "--quickPlayMultiplayer", "${quickPlayMultiplayer}",
+        ], &[
+            "--quickPlaySingleplayer", "${quickPlaySingleplayer}",
+            "--quickPlayRealms", "${quickPlayRealms}",
+        ], "all features");
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        fn rules(json: serde_json::Value) -> Vec<Rule> {
+            serde_json::from_value(json).unwrap()
+        }
+        let chain = rules(json!([
+            {"action": "allow"},
+            {"action": "disallow", "os": {"name": "osx"}},
+            {"action": "allow", "os": {"name": "osx", "version": "^10\\.5\\.\\d$"}}
+        ]));
+        assert!(check_rule(&chain, &linux(), &Features::default()));
+        assert!(!check_rule(&chain, &osx(), &Features::default()));
+        assert!(check_rule(&chain, &Platform::new("osx", "10.5.8", "x86"), &Features::default()));
+        // 架构要完全相同，不能只是包含
+        let x86 = rules(json!([{"action": "allow", "os": {"arch": "x86"}}]));
+        assert!(!check_rule(&x86, &linux(), &Features::default()));
+        assert!(check_rule(&x86, &windows_x86(), &Features::default()));
+        // 写错的正则表达式不匹配
+        let invalid = rules(json!([{"action": "allow", "os": {"version": "("}}]));
+        assert!(!check_rule(&invalid, &linux(), &Features::default()));
+        assert!(!check_rule(&[], &linux(), &Features::default()));
+    }
+}
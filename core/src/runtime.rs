@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::env::consts::{ARCH, OS};
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use reqwest::blocking::Client;
+use serde_json::Value;
+use crate::download::DownloadTask;
+use crate::error::missing;
+use crate::java::{executable_in, JavaInstallation};
+use crate::model::Version;
+use crate::progress::{CancelToken, InstallPhase, ProgressSink, Tracker};
+use crate::scheduler::{Job, Scheduler};
+use crate::source::DownloadSources;
+use crate::{CoreError, Result};
+
+// Mojang的爪哇运行时清单
+pub const RUNTIME_MANIFEST: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+// 清单里当前平台的名字
+pub fn platform() -> Option<&'static str> {
+    match (OS, ARCH) {
+        ("windows", "x86_64") => Some("windows-x64"),
+        ("windows", "x86") => Some("windows-x86"),
+        ("windows", "aarch64") => Some("windows-arm64"),
+        ("linux", "x86_64") => Some("linux"),
+        ("linux", "x86") => Some("linux-i386"),
+        ("macos", "x86_64") => Some("mac-os"),
+        ("macos", "aarch64") => Some("mac-os-arm64"),
+        _ => None,
+    }
+}
+
+// 版本Json要求的运行时组件（java-runtime-gamma等），老版本使用jre-legacy
+pub fn component_for(version: &Version) -> String {
+    String::from(version.java_version.as_ref().and_then(|j| j.component.as_deref()).unwrap_or("jre-legacy"))
+}
+
+// 可以安装的运行时
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeComponent {
+    pub component: String,
+    pub version: String,
+    pub manifest_url: String,
+    pub manifest_sha1: Option<String>,
+}
+
+// 官方爪哇运行时的安装器
+pub struct JavaRuntimes {
+    manifest_url: String,
+    runtime_dir: PathBuf,
+    sources: DownloadSources,
+    client: Client,
+    pool_size: usize,
+    retries: usize,
+}
+
+impl JavaRuntimes {
+    pub fn new(runtime_dir: impl Into<PathBuf>) -> JavaRuntimes {
+        JavaRuntimes {
+            manifest_url: String::from(RUNTIME_MANIFEST),
+            runtime_dir: runtime_dir.into(),
+            sources: DownloadSources::official(),
+            client: Client::new(),
+            pool_size: 8,
+            retries: 3,
+        }
+    }
+
+    pub fn with_manifest_url(mut self, url: impl Into<String>) -> JavaRuntimes {
+        self.manifest_url = url.into();
+        self
+    }
+
+    pub fn with_sources(mut self, sources: DownloadSources) -> JavaRuntimes {
+        self.sources = sources;
+        self
+    }
+
+    pub fn with_client(mut self, client: Client) -> JavaRuntimes {
+        self.client = client;
+        self
+    }
+
+    pub fn with_pool_size(mut self, pool_size: usize) -> JavaRuntimes {
+        self.pool_size = pool_size;
+        self
+    }
+
+    pub fn with_retries(mut self, retries: usize) -> JavaRuntimes {
+        self.retries = retries;
+        self
+    }
+
+    // 运行时的安装目录，与官方启动器相同：<组件>/<平台>/<组件>
+    pub fn home(&self, component: &str) -> Result<PathBuf> {
+        let platform = platform().ok_or_else(|| CoreError::Java(format!("Unsupported platform {}-{}!", OS, ARCH)))?;
+        Ok(self.runtime_dir.join(component).join(platform).join(component))
+    }
+
+    // 当前平台可以安装的运行时
+    pub fn list(&self) -> Result<Vec<RuntimeComponent>> {
+        let platform = platform().ok_or_else(|| CoreError::Java(format!("Unsupported platform {}-{}!", OS, ARCH)))?;
+        let all: Value = serde_json::from_str(&self.sources.get_text(&self.client, &self.manifest_url)?)?;
+        let mut result = Vec::new();
+        for (component, entries) in all[platform].as_object().ok_or_else(|| missing(platform))? {
+            // 每个组件只取第一个（最新的）
+            let Some(entry) = entries.as_array().and_then(|e| e.first()) else {
+                continue
+            };
+            result.push(RuntimeComponent {
+                component: component.clone(),
+                version: String::from(entry["version"]["name"].as_str().unwrap_or_default()),
+                manifest_url: String::from(entry["manifest"]["url"].as_str().ok_or_else(|| missing("manifest.url"))?),
+                manifest_sha1: entry["manifest"]["sha1"].as_str().map(String::from),
+            });
+        }
+        result.sort_by(|a, b| a.component.cmp(&b.component));
+        Ok(result)
+    }
+
+    // 安装一个运行时并返回安装好的爪哇
+    pub fn install(&self, component: &str, progress: Arc<dyn ProgressSink>, cancel: &CancelToken) -> Result<JavaInstallation> {
+        let tracker = Tracker::new(progress);
+        tracker.phase(InstallPhase::Json);
+        let runtime = self.list()?
+            .into_iter()
+            .find(|r| r.component == component)
+            .ok_or_else(|| CoreError::Java(format!("Java runtime \"{}\" is not available for this platform!", component)))?;
+        let home = self.home(component)?;
+
+        // 组件清单
+        let manifest_path = self.runtime_dir.join(format!("{}.json", component));
+        let task = DownloadTask::new(runtime.manifest_url.clone(), manifest_path.clone())
+            .with_sha1(runtime.manifest_sha1.as_deref());
+        let scheduler = Scheduler::new(self.client.clone(), self.sources.clone())
+            .with_pool_size(self.pool_size)
+            .with_retries(self.retries);
+        scheduler.run_one(task, &tracker, cancel)?;
+        let manifest: Value = serde_json::from_str(&std::fs::read_to_string(&manifest_path)?)?;
+        let files = manifest["files"].as_object().ok_or_else(|| missing("files"))?;
+
+        // 先创建目录，再下载文件，最后创建链接
+        tracker.phase(InstallPhase::Libraries);
+        let mut jobs = Vec::new();
+        let mut links: HashMap<PathBuf, String> = HashMap::new();
+        for (name, file) in files {
+            let path = home.join(name);
+            if !path.starts_with(&home) || name.contains("..") {
+                return Err(CoreError::Manifest(format!("Invalid runtime file path \"{}\"!", name)));
+            }
+            match file["type"].as_str() {
+                Some("directory") => create_dir_all(&path)?,
+                Some("file") => {
+                    let task = DownloadTask::from_json(&file["downloads"]["raw"], path)?;
+                    let job = if file["executable"].as_bool() == Some(true) {
+                        Job::new(task).then(|task| set_executable(&task.path))
+                    } else {
+                        Job::new(task)
+                    };
+                    jobs.push(job);
+                }
+                Some("link") => {
+                    links.insert(path, String::from(file["target"].as_str().ok_or_else(|| missing("target"))?));
+                }
+                _ => {}
+            }
+        }
+        let failed = scheduler.run(jobs, &tracker, cancel)?;
+        if let Some((task, e)) = failed.into_iter().next() {
+            return Err(CoreError::Java(format!("Could not download {:?}: {}", task.path, e)));
+        }
+        for (path, target) in links {
+            create_link(&path, &target)?;
+        }
+        tracker.phase(InstallPhase::Done);
+
+        // macOS的运行时在jre.bundle/Contents/Home里
+        let java = executable_in(&home).or_else(|| executable_in(&home.join("jre.bundle")))
+            .ok_or_else(|| CoreError::Java(format!("Could not find java in installed runtime {:?}!", home)))?;
+        JavaInstallation::probe(&java)
+            .ok_or_else(|| CoreError::Java(format!("Installed runtime {:?} is not usable!", java)))
+    }
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_link(path: &Path, target: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    if path.symlink_metadata().is_ok() {
+        std::fs::remove_file(path)?;
+    }
+    std::os::unix::fs::symlink(target, path)?;
+    Ok(())
+}
+
+// Windows上的运行时没有链接
+#[cfg(not(unix))]
+fn create_link(_path: &Path, _target: &str) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::download::file_sha1;
+    use crate::progress::NoProgress;
+    use crate::test_server::{Response, TestServer};
+
+    const JAVA_SCRIPT: &str = "#!/bin/sh\necho '    java.home = /fake' >&2\necho '    java.version = 17.0.8' >&2\necho '    java.vendor = Mojang' >&2\necho '    os.arch = amd64' >&2\n";
+    const JAVA_SHA1: &str = "0000000000000000000000000000000000000000";
+
+    fn sha1_of(data: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("akiraka-runtime-sha1-{}", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        file_sha1(&path).unwrap()
+    }
+
+    // prefix是运行时里bin目录的位置，macOS上是jre.bundle/Contents/Home/
+    fn fixture_server(java_sha1: String, prefix: &'static str) -> TestServer {
+        let base = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+        let b = base.clone();
+        let server = TestServer::start(move |req| {
+            let base = b.lock().unwrap().clone();
+            match req.path.as_str() {
+                "/all.json" => Response::json(200, &json!({
+                    platform().unwrap(): {
+                        "java-runtime-gamma": [{
+                            "manifest": {"url": format!("{}/gamma.json", base)},
+                            "version": {"name": "17.0.8", "released": "2023-08-01T00:00:00+00:00"}
+                        }],
+                        "jre-legacy": []
+                    }
+                })),
+                "/gamma.json" => Response::json(200, &json!({
+                    "files": {
+                        format!("{}bin", prefix): {"type": "directory"},
+                        format!("{}bin/java", prefix): {
+                            "type": "file",
+                            "executable": true,
+                            "downloads": {"raw": {"url": format!("{}/java", base), "sha1": java_sha1, "size": JAVA_SCRIPT.len()}}
+                        },
+                        "lib": {"type": "directory"},
+                        "legal/LICENSE": {"type": "link", "target": format!("../{}bin/java", prefix)}
+                    }
+                })),
+                "/java" => Response::ok(JAVA_SCRIPT),
+                _ => Response::not_found(),
+            }
+        });
+        *base.lock().unwrap() = server.base_url.clone();
+        server
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("akiraka-runtime-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn lists_components_for_platform() {
+        let server = fixture_server(String::from(JAVA_SHA1), "");
+        let runtimes = JavaRuntimes::new(temp_dir("list")).with_manifest_url(server.url("/all.json"));
+        let list = runtimes.list().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].component, "java-runtime-gamma");
+        assert_eq!(list[0].version, "17.0.8");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn installs_runtime_from_manifest() {
+        for (name, prefix) in [("install", ""), ("install-mac", "jre.bundle/Contents/Home/")] {
+            let server = fixture_server(sha1_of(JAVA_SCRIPT.as_bytes()), prefix);
+            let dir = temp_dir(name);
+            let runtimes = JavaRuntimes::new(&dir).with_manifest_url(server.url("/all.json"));
+            let java = runtimes.install("java-runtime-gamma", Arc::new(NoProgress), &CancelToken::new()).unwrap();
+
+            let home = runtimes.home("java-runtime-gamma").unwrap();
+            let executable = home.join(prefix).join("bin/java");
+            assert_eq!(java.path, executable);
+            assert_eq!(java.info.major_version, 17);
+            assert!(home.join("lib").is_dir());
+            assert_eq!(std::fs::read_link(home.join("legal/LICENSE")).unwrap(), PathBuf::from(format!("../{}bin/java", prefix)));
+            // 可以被爪哇搜索找到
+            assert!(crate::java::candidates(Some(&dir)).contains(&executable));
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_files() {
+        let server = fixture_server(String::from(JAVA_SHA1), "");
+        let runtimes = JavaRuntimes::new(temp_dir("corrupt"))
+            .with_manifest_url(server.url("/all.json"))
+            .with_retries(0);
+        // 校验和不对
+        assert!(matches!(
+            runtimes.install("java-runtime-gamma", Arc::new(NoProgress), &CancelToken::new()),
+            Err(CoreError::Java(_))
+        ));
+        // 当前平台没有这个组件
+        assert!(matches!(
+            runtimes.install("jre-legacy", Arc::new(NoProgress), &CancelToken::new()),
+            Err(CoreError::Java(_))
+        ));
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use reqwest::blocking::Client;
+use crate::download::{download, DownloadStatus, DownloadTask};
+use crate::progress::{CancelToken, Tracker};
+use crate::source::DownloadSources;
+use crate::{CoreError, Result};
+
+// 下载完成后要做的事情（比如解压Native库）
+pub type AfterDownload = Box<dyn FnOnce(&DownloadTask) -> Result<()> + Send>;
+
+// 调度器里的一个任务
+pub struct Job {
+    pub task: DownloadTask,
+    pub after: Option<AfterDownload>,
+}
+
+impl Job {
+    pub fn new(task: DownloadTask) -> Job {
+        Job { task, after: None }
+    }
+
+    pub fn then(mut self, after: impl FnOnce(&DownloadTask) -> Result<()> + Send + 'static) -> Job {
+        self.after = Some(Box::new(after));
+        self
+    }
+}
+
+impl From<DownloadTask> for Job {
+    fn from(task: DownloadTask) -> Self {
+        Job::new(task)
+    }
+}
+
+// 每个主机的连接数限制
+struct HostLimiter {
+    limit: usize,
+    active: Mutex<HashMap<String, usize>>,
+    released: Condvar,
+}
+
+impl HostLimiter {
+    fn new(limit: usize) -> HostLimiter {
+        HostLimiter {
+            limit: limit.max(1),
+            active: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self, host: &str) -> HostPermit<'_> {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        while active.get(host).copied().unwrap_or(0) >= self.limit {
+            active = self.released.wait(active).unwrap_or_else(|e| e.into_inner());
+        }
+        *active.entry(String::from(host)).or_insert(0) += 1;
+        HostPermit { limiter: self, host: String::from(host) }
+    }
+}
+
+struct HostPermit<'a> {
+    limiter: &'a HostLimiter,
+    host: String,
+}
+
+impl Drop for HostPermit<'_> {
+    fn drop(&mut self) {
+        let mut active = self.limiter.active.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = active.get_mut(&self.host) {
+            *count -= 1;
+        }
+        self.limiter.released.notify_all();
+    }
+}
+
+fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| match u.port() {
+            Some(port) => format!("{}:{}", h, port),
+            None => String::from(h),
+        }))
+        .unwrap_or_default()
+}
+
+// 下载调度器：固定数量的线程，每个主机限制连接数，失败时退避重试
+pub struct Scheduler {
+    client: Client,
+    sources: DownloadSources,
+    pool_size: usize,
+    per_host: usize,
+    retries: usize,
+    backoff: Duration,
+}
+
+impl Scheduler {
+    pub fn new(client: Client, sources: DownloadSources) -> Scheduler {
+        Scheduler {
+            client,
+            sources,
+            pool_size: 8,
+            per_host: 8,
+            retries: 3,
+            backoff: Duration::from_millis(500),
+        }
+    }
+
+    pub fn with_pool_size(mut self, pool_size: usize) -> Scheduler {
+        self.pool_size = pool_size.max(1);
+        self
+    }
+
+    pub fn with_per_host(mut self, per_host: usize) -> Scheduler {
+        self.per_host = per_host.max(1);
+        self
+    }
+
+    pub fn with_retries(mut self, retries: usize) -> Scheduler {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff: Duration) -> Scheduler {
+        self.backoff = backoff;
+        self
+    }
+
+    // 等待一段时间，期间可以被取消
+    fn sleep(&self, duration: Duration, cancel: &CancelToken) -> Result<()> {
+        let step = Duration::from_millis(50);
+        let mut left = duration;
+        while !left.is_zero() {
+            cancel.check()?;
+            let now = left.min(step);
+            thread::sleep(now);
+            left -= now;
+        }
+        Ok(())
+    }
+
+    // 下载单个文件：依次尝试每个下载源，每个源失败时退避重试
+    fn fetch(&self, task: &DownloadTask, limiter: &HostLimiter, cancel: &CancelToken) -> Result<DownloadStatus> {
+        let mut last_error = None;
+        for url in self.sources.urls(&task.url) {
+            let mut task = task.clone();
+            task.url = url;
+            let host = host_of(&task.url);
+            for attempt in 0..=self.retries {
+                if attempt > 0 {
+                    self.sleep(self.backoff * 2u32.pow(attempt as u32 - 1), cancel)?;
+                }
+                let result = {
+                    let _permit = limiter.acquire(&host);
+                    download(&self.client, &task, 0, cancel)
+                };
+                match result {
+                    Ok(status) => return Ok(status),
+                    Err(e) if e.is_retryable() => last_error = Some(e),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| CoreError::Manifest(format!("No download source for {}", task.url))))
+    }
+
+    // 运行所有任务，全部完成或失败后才返回，返回失败的任务
+    pub fn run(&self, jobs: Vec<Job>, tracker: &Tracker, cancel: &CancelToken) -> Result<Vec<(DownloadTask, CoreError)>> {
+        for job in &jobs {
+            tracker.add(&job.task);
+        }
+        let queue = Mutex::new(jobs.into_iter().collect::<VecDeque<Job>>());
+        let failed = Mutex::new(Vec::new());
+        let limiter = HostLimiter::new(self.per_host);
+
+        thread::scope(|scope| {
+            for _ in 0..self.pool_size {
+                scope.spawn(|| loop {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+                    let Some(job) = queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front() else {
+                        break;
+                    };
+                    let result = self.fetch(&job.task, &limiter, cancel)
+                        .and_then(|_| match job.after {
+                            Some(after) => after(&job.task),
+                            None => Ok(()),
+                        });
+                    match result {
+                        Ok(_) => tracker.complete(&job.task),
+                        Err(CoreError::Cancelled) => break,
+                        Err(e) => {
+                            tracker.fail(&job.task, &e);
+                            failed.lock().unwrap_or_else(|e| e.into_inner()).push((job.task, e));
+                        }
+                    }
+                });
+            }
+        });
+
+        cancel.check()?;
+        Ok(failed.into_inner().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    // 只下载一个文件，失败时返回错误
+    pub fn run_one(&self, task: DownloadTask, tracker: &Tracker, cancel: &CancelToken) -> Result<()> {
+        tracker.add(&task);
+        match self.fetch(&task, &HostLimiter::new(self.per_host), cancel) {
+            Ok(_) => {
+                tracker.complete(&task);
+                Ok(())
+            }
+            Err(e) => {
+                tracker.fail(&task, &e);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::progress::NoProgress;
+    use crate::test_server::{Response, TestServer};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("akiraka-scheduler-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn respects_per_host_limit_and_waits_for_all() {
+        let dir = temp_dir("limit");
+        let active = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        let (a, m) = (active.clone(), max.clone());
+        let server = TestServer::start(move |req| {
+            let now = a.fetch_add(1, Ordering::SeqCst) + 1;
+            m.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(30));
+            a.fetch_sub(1, Ordering::SeqCst);
+            Response::ok(req.path.clone())
+        });
+        let jobs = (0..12)
+            .map(|i| Job::new(DownloadTask::new(server.url(&format!("/{}", i)), dir.join(i.to_string()))))
+            .collect();
+        let scheduler = Scheduler::new(Client::new(), DownloadSources::official())
+            .with_pool_size(6)
+            .with_per_host(2);
+        let tracker = Tracker::new(Arc::new(NoProgress));
+        let failed = scheduler.run(jobs, &tracker, &CancelToken::new()).unwrap();
+
+        assert!(failed.is_empty());
+        assert!(max.load(Ordering::SeqCst) <= 2);
+        for i in 0..12 {
+            assert_eq!(std::fs::read_to_string(dir.join(i.to_string())).unwrap(), format!("/{}", i));
+        }
+        assert_eq!(tracker.snapshot().completed_files, 12);
+    }
+
+    #[test]
+    fn retries_with_backoff_then_reports_failure() {
+        let dir = temp_dir("retry");
+        let count = Arc::new(AtomicUsize::new(0));
+        let c = count.clone();
+        let server = TestServer::start(move |req| {
+            if req.path == "/flaky" && c.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Response::new(503, "busy");
+            }
+            if req.path == "/flaky" {
+                return Response::ok("ok");
+            }
+            Response::not_found()
+        });
+        let jobs = vec![
+            Job::new(DownloadTask::new(server.url("/flaky"), dir.join("flaky"))),
+            Job::new(DownloadTask::new(server.url("/missing"), dir.join("missing"))),
+        ];
+        let scheduler = Scheduler::new(Client::new(), DownloadSources::official())
+            .with_retries(2)
+            .with_backoff(Duration::from_millis(1));
+        let tracker = Tracker::new(Arc::new(NoProgress));
+        let failed = scheduler.run(jobs, &tracker, &CancelToken::new()).unwrap();
+
+        assert_eq!(std::fs::read_to_string(dir.join("flaky")).unwrap(), "ok");
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].0.url.ends_with("/missing"));
+        assert_eq!(server.requests().iter().filter(|r| r.ends_with("/missing")).count(), 3);
+    }
+}
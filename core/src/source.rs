@@ -0,0 +1,221 @@
+use std::sync::Arc;
+use reqwest::blocking::Client;
+use crate::download::{download, DownloadStatus, DownloadTask};
+use crate::progress::CancelToken;
+use crate::{CoreError, Result};
+
+// 官方的地址
+pub const PISTON_META: &str = "https://piston-meta.mojang.com";
+pub const LAUNCHER_META: &str = "https://launchermeta.mojang.com";
+pub const LAUNCHER: &str = "https://launcher.mojang.com";
+pub const PISTON_DATA: &str = "https://piston-data.mojang.com";
+pub const RESOURCES: &str = "https://resources.download.minecraft.net";
+pub const LIBRARIES: &str = "https://libraries.minecraft.net";
+pub const AUTHLIB_INJECTOR: &str = "https://authlib-injector.org/artifact";
+pub const FABRIC_META: &str = "https://meta.fabricmc.net";
+pub const QUILT_META: &str = "https://meta.quiltmc.org";
+pub const FORGE_MAVEN: &str = "https://maven.minecraftforge.net";
+pub const NEOFORGE_MAVEN: &str = "https://maven.neoforged.net/releases";
+
+pub const BMCLAPI: &str = "https://bmclapi2.bangbang93.com";
+
+// 下载源
+pub trait DownloadSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    // 把官方地址改写为这个下载源的地址，不支持时返回None
+    fn rewrite(&self, url: &str) -> Option<String>;
+}
+
+// 官方源，原样返回
+pub struct Official;
+
+impl DownloadSource for Official {
+    fn name(&self) -> &str {
+        "Official"
+    }
+
+    fn rewrite(&self, url: &str) -> Option<String> {
+        Some(String::from(url))
+    }
+}
+
+// 按前缀替换地址的镜像源
+pub struct Mirror {
+    name: String,
+    rules: Vec<(String, String)>,
+}
+
+impl Mirror {
+    pub fn new(name: impl Into<String>) -> Mirror {
+        Mirror {
+            name: name.into(),
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn with_rule(mut self, from: impl Into<String>, to: impl Into<String>) -> Mirror {
+        self.rules.push((from.into(), to.into()));
+        self
+    }
+
+    // BMCLAPI格式的镜像，base为镜像根地址
+    pub fn bmclapi_compatible(name: impl Into<String>, base: &str) -> Mirror {
+        let base = base.trim_end_matches('/');
+        Mirror::new(name)
+            .with_rule(PISTON_META, base)
+            .with_rule(LAUNCHER_META, base)
+            .with_rule(LAUNCHER, base)
+            .with_rule(PISTON_DATA, base)
+            .with_rule(RESOURCES, format!("{}/assets", base))
+            .with_rule(LIBRARIES, format!("{}/maven", base))
+            .with_rule(FORGE_MAVEN, format!("{}/maven", base))
+            .with_rule("https://files.minecraftforge.net/maven", format!("{}/maven", base))
+            .with_rule(NEOFORGE_MAVEN, format!("{}/maven", base))
+            .with_rule("https://maven.fabricmc.net", format!("{}/maven", base))
+            .with_rule(FABRIC_META, format!("{}/fabric-meta", base))
+            .with_rule(QUILT_META, format!("{}/quilt-meta", base))
+            .with_rule(AUTHLIB_INJECTOR, format!("{}/mirrors/authlib-injector/artifact", base))
+    }
+
+    pub fn bmclapi() -> Mirror {
+        Mirror::bmclapi_compatible("BMCLAPI", BMCLAPI)
+    }
+
+    // 用户自定义的镜像根地址
+    pub fn custom(base: &str) -> Mirror {
+        Mirror::bmclapi_compatible("Custom", base)
+    }
+}
+
+impl DownloadSource for Mirror {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn rewrite(&self, url: &str) -> Option<String> {
+        self.rules.iter()
+            .find(|(from, _)| url.starts_with(from.as_str()))
+            .map(|(from, to)| format!("{}{}", to, &url[from.len()..]))
+    }
+}
+
+// 按顺序尝试的下载源列表
+#[derive(Clone)]
+pub struct DownloadSources {
+    sources: Vec<Arc<dyn DownloadSource>>,
+}
+
+impl Default for DownloadSources {
+    fn default() -> Self {
+        DownloadSources::official()
+    }
+}
+
+impl DownloadSources {
+    pub fn new(sources: Vec<Arc<dyn DownloadSource>>) -> DownloadSources {
+        DownloadSources { sources }
+    }
+
+    pub fn official() -> DownloadSources {
+        DownloadSources::new(vec![Arc::new(Official)])
+    }
+
+    // 优先使用镜像，失败时回退到官方源
+    pub fn with_fallback(primary: impl DownloadSource + 'static) -> DownloadSources {
+        DownloadSources::new(vec![Arc::new(primary), Arc::new(Official)])
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.sources.iter().map(|s| s.name()).collect()
+    }
+
+    // 所有候选地址，按优先级排列并去重
+    pub fn urls(&self, url: &str) -> Vec<String> {
+        let mut urls: Vec<String> = Vec::new();
+        for source in &self.sources {
+            if let Some(rewritten) = source.rewrite(url) {
+                if !urls.contains(&rewritten) {
+                    urls.push(rewritten);
+                }
+            }
+        }
+        if urls.is_empty() {
+            urls.push(String::from(url));
+        }
+        urls
+    }
+
+    // 依次尝试每个下载源
+    fn try_each<T>(&self, url: &str, mut f: impl FnMut(&str) -> Result<T>) -> Result<T> {
+        let mut last_error = None;
+        for candidate in self.urls(url) {
+            match f(&candidate) {
+                Ok(res) => return Ok(res),
+                Err(e) if e.is_retryable() => last_error = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| CoreError::Manifest(format!("No download source for {}", url))))
+    }
+
+    // 获取文本（版本清单、版本Json等）
+    pub fn get_text(&self, client: &Client, url: &str) -> Result<String> {
+        self.try_each(url, |url| Ok(client.get(url).send()?.error_for_status()?.text()?))
+    }
+
+    // 下载文件，当前源失败时换下一个
+    pub fn download(&self, client: &Client, task: &DownloadTask, retries: usize, cancel: &CancelToken) -> Result<DownloadStatus> {
+        self.try_each(&task.url, |url| {
+            let mut task = task.clone();
+            task.url = String::from(url);
+            download(client, &task, retries, cancel)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::TestServer;
+
+    #[test]
+    fn bmclapi_rewrites_official_urls() {
+        let mirror = Mirror::bmclapi();
+        assert_eq!(
+            mirror.rewrite("https://piston-meta.mojang.com/mc/game/version_manifest.json").unwrap(),
+            "https://bmclapi2.bangbang93.com/mc/game/version_manifest.json"
+        );
+        assert_eq!(
+            mirror.rewrite("https://resources.download.minecraft.net/ab/abcdef").unwrap(),
+            "https://bmclapi2.bangbang93.com/assets/ab/abcdef"
+        );
+        assert_eq!(
+            mirror.rewrite("https://libraries.minecraft.net/com/mojang/brigadier/1.0.18/brigadier-1.0.18.jar").unwrap(),
+            "https://bmclapi2.bangbang93.com/maven/com/mojang/brigadier/1.0.18/brigadier-1.0.18.jar"
+        );
+        assert!(mirror.rewrite("https://example.com/a.jar").is_none());
+    }
+
+    #[test]
+    fn urls_fall_back_to_official() {
+        let sources = DownloadSources::with_fallback(Mirror::custom("http://mirror.local/"));
+        assert_eq!(sources.urls("https://libraries.minecraft.net/a.jar"), vec![
+            String::from("http://mirror.local/maven/a.jar"),
+            String::from("https://libraries.minecraft.net/a.jar"),
+        ]);
+        assert_eq!(sources.urls("https://example.com/a.jar"), vec![String::from("https://example.com/a.jar")]);
+    }
+
+    #[test]
+    fn falls_back_when_primary_fails() {
+        let broken = TestServer::start(|_| crate::test_server::Response::new(500, "oops"));
+        let working = TestServer::with_files(vec![("/a.txt", b"hello".to_vec())]);
+        let sources = DownloadSources::new(vec![
+            Arc::new(Mirror::new("Broken").with_rule("https://origin", broken.base_url.clone())),
+            Arc::new(Mirror::new("Working").with_rule("https://origin", working.base_url.clone())),
+        ]);
+        assert_eq!(sources.get_text(&Client::new(), "https://origin/a.txt").unwrap(), "hello");
+        assert_eq!(broken.requests(), vec![String::from("GET /a.txt")]);
+    }
+}
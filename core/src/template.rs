@@ -0,0 +1,98 @@
+use std::collections::{BTreeSet, HashMap};
+use crate::{CoreError, Result};
+
+// 启动参数里的${...}模板
+#[derive(Clone, Debug, Default)]
+pub struct Template {
+    values: HashMap<String, String>,
+}
+
+impl Template {
+    pub fn new() -> Template {
+        Template::default()
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Into<String>) -> &mut Template {
+        self.values.insert(String::from(key), value.into());
+        self
+    }
+
+    pub fn with(mut self, key: &str, value: impl Into<String>) -> Template {
+        self.set(key, value);
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    // 替换一个参数，未知的变量名放进unknown里，原样保留
+    fn render_into(&self, arg: &str, unknown: &mut BTreeSet<String>) -> String {
+        let mut out = String::with_capacity(arg.len());
+        let mut rest = arg;
+        while let Some(start) = rest.find("${") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let Some(end) = after.find('}') else {
+                // 没有闭合的括号，不是变量
+                out.push_str(&rest[start..]);
+                return out;
+            };
+            let key = &after[..end];
+            match self.values.get(key) {
+                Some(value) => out.push_str(value),
+                None => {
+                    unknown.insert(String::from(key));
+                    out.push_str(&rest[start..start + 2 + end + 1]);
+                }
+            }
+            rest = &after[end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    pub fn render(&self, arg: &str) -> Result<String> {
+        let mut unknown = BTreeSet::new();
+        let out = self.render_into(arg, &mut unknown);
+        if unknown.is_empty() {
+            Ok(out)
+        } else {
+            Err(CoreError::UnknownPlaceholders(unknown.into_iter().collect()))
+        }
+    }
+
+    // 替换所有参数，有未知变量时一起报告
+    pub fn render_all(&self, args: &[String]) -> Result<Vec<String>> {
+        let mut unknown = BTreeSet::new();
+        let out = args.iter().map(|arg| self.render_into(arg, &mut unknown)).collect();
+        if unknown.is_empty() {
+            Ok(out)
+        } else {
+            Err(CoreError::UnknownPlaceholders(unknown.into_iter().collect()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_and_reports_unknown() {
+        let template = Template::new()
+            .with("auth_player_name", "Steve")
+            .with("resolution_width", "854")
+            .with("classpath_separator", ":");
+        assert_eq!(template.render("--username=${auth_player_name}").unwrap(), "--username=Steve");
+        assert_eq!(template.render("a${classpath_separator}b${classpath_separator}").unwrap(), "a:b:");
+        assert_eq!(template.render("${unclosed").unwrap(), "${unclosed");
+        assert_eq!(template.render("$100").unwrap(), "$100");
+
+        let args = vec![String::from("${resolution_width}"), String::from("${foo}x${bar}"), String::from("${foo}")];
+        match template.render_all(&args) {
+            Err(CoreError::UnknownPlaceholders(keys)) => assert_eq!(keys, vec!["bar", "foo"]),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
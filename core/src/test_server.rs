@@ -0,0 +1,146 @@
+#![allow(dead_code)]
+// 测试用的本地Http服务器
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers.get(&key.to_lowercase()).map(String::as_str)
+    }
+
+    pub fn body_str(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Response {
+        Response { status, headers: Vec::new(), body: body.into() }
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Response {
+        Response::new(200, body)
+    }
+
+    pub fn json(status: u16, body: &serde_json::Value) -> Response {
+        Response::new(status, body.to_string()).with_header("Content-Type", "application/json")
+    }
+
+    pub fn not_found() -> Response {
+        Response::new(404, "Not Found")
+    }
+
+    pub fn with_header(mut self, key: &str, value: &str) -> Response {
+        self.headers.push((String::from(key), String::from(value)));
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+pub struct TestServer {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestServer {
+    pub fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind test server!");
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handler: Arc<Handler> = Arc::new(handler);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue
+                };
+                let handler = handler.clone();
+                let log = log.clone();
+                thread::spawn(move || serve(stream, &*handler, &log));
+            }
+        });
+        TestServer { base_url, requests }
+    }
+
+    // 静态文件，支持Range请求
+    pub fn with_files(files: Vec<(&str, Vec<u8>)>) -> TestServer {
+        let files: HashMap<String, Vec<u8>> = files.into_iter().map(|(k, v)| (String::from(k), v)).collect();
+        TestServer::start(move |req| {
+            let Some(data) = files.get(&req.path) else {
+                return Response::not_found();
+            };
+            if let Some(start) = req.header("range")
+                .and_then(|r| r.strip_prefix("bytes="))
+                .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok()) {
+                if start < data.len() {
+                    return Response::new(206, data[start..].to_vec())
+                        .with_header("Content-Range", &format!("bytes {}-{}/{}", start, data.len() - 1, data.len()));
+                }
+            }
+            Response::ok(data.clone())
+        })
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    // 已收到的请求，格式为 "METHOD /path"
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn serve(mut stream: TcpStream, handler: &Handler, log: &Mutex<Vec<String>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() {
+        return;
+    }
+    let mut parts = line.split_whitespace();
+    let method = String::from(parts.next().unwrap_or_default());
+    let path = String::from(parts.next().unwrap_or_default());
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() || line.trim().is_empty() {
+            break;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            headers.insert(k.trim().to_lowercase(), String::from(v.trim()));
+        }
+    }
+    let length = headers.get("content-length").and_then(|l| l.parse::<usize>().ok()).unwrap_or(0);
+    let mut body = vec![0; length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+    log.lock().unwrap().push(format!("{} {}", method, path));
+
+    let response = handler(&Request { method, path, headers, body });
+    let mut head = format!("HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n", response.status, response.body.len());
+    for (k, v) in &response.headers {
+        head += &format!("{}: {}\r\n", k, v);
+    }
+    head += "\r\n";
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(&response.body);
+    let _ = stream.flush();
+}
@@ -1,12 +1,9 @@
 use std::env;
+use crate::{CoreError, Result};
 
-pub fn current_dir() -> String {
-    return match env::current_dir() {
-        Ok(path) => {
-            path.to_str().unwrap().to_string()
-        },
-        Err(e) => {
-            panic!("Failed to get current path: {}", e);
-        }
-    };
-}
\ No newline at end of file
+pub fn current_dir() -> Result<String> {
+    let path = env::current_dir()?;
+    path.to_str()
+        .map(String::from)
+        .ok_or_else(|| CoreError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, "Current path is not valid UTF-8!")))
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+use std::env::consts::OS;
+use std::fs::{create_dir_all, read_to_string, remove_file, rename, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::{CoreError, Result};
+
+// 默认的PBKDF2迭代次数
+pub const DEFAULT_ITERATIONS: u32 = 200_000;
+
+// 用来确认密钥是否正确的内容
+const CHECK: &[u8] = b"akiraka-vault";
+
+// 加密密钥的来源
+#[derive(Clone)]
+pub enum VaultKey {
+    // 用户输入的密码
+    Passphrase(String),
+    // 本机的机器码，换电脑后无法解密
+    Machine,
+}
+
+impl VaultKey {
+    fn secret(&self) -> Result<String> {
+        match self {
+            VaultKey::Passphrase(passphrase) => Ok(passphrase.clone()),
+            VaultKey::Machine => machine_secret(),
+        }
+    }
+}
+
+// 运行命令并取出输出里key后面的值
+fn command_value(program: &str, args: &[&str], key: &str) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().find(|l| l.contains(key))?;
+    let value = line.split(key).nth(1)?.trim().trim_start_matches(['=', ' ']).trim_matches('"');
+    (!value.is_empty()).then(|| String::from(value))
+}
+
+// 本机的机器码，找不到时报错，不用主机名等公开信息代替
+fn machine_secret() -> Result<String> {
+    let id = match OS {
+        // 64位注册表里的MachineGuid
+        "windows" => command_value("reg", &["query", r"HKLM\SOFTWARE\Microsoft\Cryptography", "/v", "MachineGuid", "/reg:64"], "REG_SZ"),
+        "macos" => command_value("ioreg", &["-rd1", "-c", "IOPlatformExpertDevice"], "\"IOPlatformUUID\""),
+        _ => ["/etc/machine-id", "/var/lib/dbus/machine-id"].iter()
+            .filter_map(|path| read_to_string(path).ok())
+            .map(|id| String::from(id.trim()))
+            .find(|id| !id.is_empty()),
+    };
+    id.ok_or_else(|| CoreError::Vault(String::from("No machine secret available, use a passphrase instead")))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    let invalid = || CoreError::Vault(String::from("Invalid hex"));
+    if !hex.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    // 按字节处理，被篡改的文件里可能有非ASCII字符
+    let digit = |b: u8| (b as char).to_digit(16).ok_or_else(invalid);
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| Ok((digit(pair[0])? * 16 + digit(pair[1])?) as u8))
+        .collect()
+}
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+// 加密后的一条数据
+#[derive(Clone, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    salt: String,
+    iterations: u32,
+    check: Sealed,
+    entries: BTreeMap<String, Sealed>,
+}
+
+// 派生出来的密钥，释放时清零
+struct DerivedKey([u8; 32]);
+
+impl DerivedKey {
+    fn derive(key: &VaultKey, salt: &[u8], iterations: u32) -> Result<DerivedKey> {
+        let mut derived = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(key.secret()?.as_bytes(), salt, iterations, &mut derived);
+        Ok(DerivedKey(derived))
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
+
+    // 名字作为附加数据，防止把一条数据换到另一个名字下
+    fn seal(&self, name: &str, data: &[u8]) -> Result<Sealed> {
+        let nonce = random::<12>();
+        let data = self.cipher()
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: data, aad: name.as_bytes() })
+            .map_err(|_| CoreError::Vault(String::from("Encryption failed")))?;
+        Ok(Sealed { nonce: to_hex(&nonce), data: to_hex(&data) })
+    }
+
+    fn open(&self, name: &str, sealed: &Sealed) -> Result<Vec<u8>> {
+        let nonce = from_hex(&sealed.nonce)?;
+        if nonce.len() != 12 {
+            return Err(CoreError::Vault(String::from("Invalid nonce")));
+        }
+        self.cipher()
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &from_hex(&sealed.data)?, aad: name.as_bytes() })
+            .map_err(|_| CoreError::Vault(format!("Could not decrypt \"{}\", wrong key or tampered data", name)))
+    }
+}
+
+impl Drop for DerivedKey {
+    fn drop(&mut self) {
+        for byte in self.0.iter_mut() {
+            // 防止被编译器优化掉
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+    }
+}
+
+// 加密保存令牌等凭据的文件
+pub struct Vault {
+    path: PathBuf,
+    key: DerivedKey,
+    file: VaultFile,
+}
+
+impl Vault {
+    // 打开凭据库，文件不存在时新建
+    pub fn open(path: impl Into<PathBuf>, key: &VaultKey) -> Result<Vault> {
+        Vault::open_with_iterations(path, key, DEFAULT_ITERATIONS)
+    }
+
+    // iterations只在新建时使用，已有的文件使用文件里记录的次数
+    pub fn open_with_iterations(path: impl Into<PathBuf>, key: &VaultKey, iterations: u32) -> Result<Vault> {
+        let path = path.into();
+        if !path.exists() {
+            let salt = random::<16>();
+            let derived = DerivedKey::derive(key, &salt, iterations)?;
+            let file = VaultFile {
+                version: 1,
+                salt: to_hex(&salt),
+                iterations,
+                check: derived.seal("", CHECK)?,
+                entries: BTreeMap::new(),
+            };
+            let vault = Vault { path, key: derived, file };
+            vault.save()?;
+            return Ok(vault);
+        }
+        let file: VaultFile = serde_json::from_str(&read_to_string(&path)?)?;
+        let derived = DerivedKey::derive(key, &from_hex(&file.salt)?, file.iterations)?;
+        if derived.open("", &file.check)? != CHECK {
+            return Err(CoreError::Vault(String::from("Wrong key")));
+        }
+        Ok(Vault { path, key: derived, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // 先写到同一目录下的临时文件再替换，写到一半时崩溃也不会丢失已有的数据
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&self.file)?;
+        let temp = self.path.with_extension("tmp");
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&temp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+        rename(&temp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn put(&mut self, name: &str, secret: &str) -> Result<()> {
+        let sealed = self.key.seal(name, secret.as_bytes())?;
+        self.file.entries.insert(String::from(name), sealed);
+        self.save()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        match self.file.entries.get(name) {
+            Some(sealed) => {
+                let data = self.key.open(name, sealed)?;
+                String::from_utf8(data)
+                    .map(Some)
+                    .map_err(|_| CoreError::Vault(format!("\"{}\" is not valid UTF-8", name)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.file.entries.keys().map(String::as_str).collect()
+    }
+
+    // 登出时删除，返回是否存在
+    pub fn remove(&mut self, name: &str) -> Result<bool> {
+        let existed = self.file.entries.remove(name).is_some();
+        if existed {
+            self.save()?;
+        }
+        Ok(existed)
+    }
+
+    // 删除名字以prefix开头的所有数据
+    pub fn remove_prefix(&mut self, prefix: &str) -> Result<usize> {
+        let before = self.file.entries.len();
+        self.file.entries.retain(|name, _| !name.starts_with(prefix));
+        let removed = before - self.file.entries.len();
+        if removed > 0 {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    // 换一个密钥，所有数据用新密钥重新加密
+    pub fn rekey(&mut self, key: &VaultKey) -> Result<()> {
+        let salt = random::<16>();
+        let derived = DerivedKey::derive(key, &salt, self.file.iterations)?;
+        let mut entries = BTreeMap::new();
+        for (name, sealed) in &self.file.entries {
+            let data = self.key.open(name, sealed)?;
+            entries.insert(name.clone(), derived.seal(name, &data)?);
+        }
+        self.file.salt = to_hex(&salt);
+        self.file.check = derived.seal("", CHECK)?;
+        self.file.entries = entries;
+        self.key = derived;
+        self.save()
+    }
+
+    // 删除文件，磁盘上的旧数据不会被覆盖，但里面只有密文
+    pub fn destroy(self) -> Result<()> {
+        remove_file(&self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("akiraka-vault-{}-{}.json", name, std::process::id()));
+        let _ = remove_file(&path);
+        path
+    }
+
+    fn passphrase(p: &str) -> VaultKey {
+        VaultKey::Passphrase(String::from(p))
+    }
+
+    #[test]
+    fn round_trip_and_rekey() {
+        let path = temp_path("round-trip");
+        let mut vault = Vault::open_with_iterations(&path, &passphrase("hunter2"), 10).unwrap();
+        vault.put("alice.refresh_token", "secret-refresh").unwrap();
+        vault.put("bob.refresh_token", "other").unwrap();
+        drop(vault);
+        assert!(!read_to_string(&path).unwrap().contains("secret-refresh"));
+        assert!(!path.with_extension("tmp").exists());
+
+        assert!(matches!(Vault::open(&path, &passphrase("wrong")), Err(CoreError::Vault(_))));
+        let mut vault = Vault::open(&path, &passphrase("hunter2")).unwrap();
+        assert_eq!(vault.get("alice.refresh_token").unwrap().as_deref(), Some("secret-refresh"));
+        assert_eq!(vault.get("missing").unwrap(), None);
+
+        vault.rekey(&passphrase("correct horse")).unwrap();
+        assert!(vault.remove("bob.refresh_token").unwrap());
+        drop(vault);
+        assert!(Vault::open(&path, &passphrase("hunter2")).is_err());
+        let vault = Vault::open(&path, &passphrase("correct horse")).unwrap();
+        assert_eq!(vault.names(), vec!["alice.refresh_token"]);
+        assert_eq!(vault.get("alice.refresh_token").unwrap().as_deref(), Some("secret-refresh"));
+
+        vault.destroy().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let path = temp_path("tamper");
+        let mut vault = Vault::open_with_iterations(&path, &passphrase("key"), 10).unwrap();
+        vault.put("a", "token-a").unwrap();
+        vault.put("b", "token-b").unwrap();
+        drop(vault);
+
+        // 修改密文
+        let mut json: serde_json::Value = serde_json::from_str(&read_to_string(&path).unwrap()).unwrap();
+        let data = String::from(json["entries"]["a"]["data"].as_str().unwrap());
+        let flipped = if data.starts_with('0') { "1" } else { "0" };
+        json["entries"]["a"]["data"] = serde_json::Value::from(format!("{}{}", flipped, &data[1..]));
+        // 把b的数据换到c下
+        json["entries"]["c"] = json["entries"]["b"].clone();
+
+        // 奇数位置上的非ASCII字符
+        json["entries"]["d"] = serde_json::json!({"nonce": "0é0", "data": "00"});
+        std::fs::write(&path, json.to_string()).unwrap();
+
+        let vault = Vault::open(&path, &passphrase("key")).unwrap();
+        assert!(matches!(vault.get("a"), Err(CoreError::Vault(_))));
+        assert!(matches!(vault.get("d"), Err(CoreError::Vault(_))));
+        assert_eq!(vault.get("b").unwrap().as_deref(), Some("token-b"));
+        assert!(matches!(vault.get("c"), Err(CoreError::Vault(_))));
+        let _ = remove_file(&path);
+    }
+}
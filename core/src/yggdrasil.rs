@@ -0,0 +1,383 @@
+use std::fs::{create_dir_all, read_dir, read_to_string, write, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use crate::download::DownloadTask;
+use crate::error::missing;
+use crate::progress::CancelToken;
+use crate::source::DownloadSources;
+use crate::{CoreError, Result};
+
+// authlib-injector的最新版本信息
+pub const AUTHLIB_INJECTOR_LATEST: &str = "https://authlib-injector.org/artifact/latest.json";
+
+// 游戏角色
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameProfile {
+    pub id: String,
+    pub name: String,
+}
+
+impl GameProfile {
+    fn from_json(json: &Value) -> Result<GameProfile> {
+        Ok(GameProfile {
+            id: String::from(json["id"].as_str().ok_or_else(|| missing("profile.id"))?),
+            name: String::from(json["name"].as_str().ok_or_else(|| missing("profile.name"))?),
+        })
+    }
+}
+
+// 登录后的会话，可能还没有选择角色
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct YggdrasilSession {
+    pub access_token: String,
+    pub client_token: String,
+    pub available_profiles: Vec<GameProfile>,
+    pub selected_profile: Option<GameProfile>,
+}
+
+// 已经选择角色的外置登录账户
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct YggdrasilAccount {
+    // API根地址，比如 https://example.com/api/yggdrasil
+    pub server: String,
+    pub name: String,
+    pub uuid: String,
+    pub access_token: String,
+    pub client_token: String,
+}
+
+// Yggdrasil协议的认证服务器
+pub struct YggdrasilAuth {
+    client: Client,
+    server: String,
+}
+
+impl YggdrasilAuth {
+    pub fn new(server: &str) -> YggdrasilAuth {
+        YggdrasilAuth {
+            client: Client::new(),
+            server: String::from(server.trim_end_matches('/')),
+        }
+    }
+
+    pub fn with_client(mut self, client: Client) -> YggdrasilAuth {
+        self.client = client;
+        self
+    }
+
+    // 发送请求，返回状态码和Json（204时为Null）
+    fn post(&self, path: &str, body: Value) -> Result<(u16, Value)> {
+        let response = self.client.post(format!("{}/authserver/{}", self.server, path))
+            .json(&body)
+            .send()?;
+        let status = response.status().as_u16();
+        let text = response.text()?;
+        let json = if text.trim().is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&text)?
+        };
+        Ok((status, json))
+    }
+
+    // 把服务器返回的错误转换为CoreError
+    fn check(status: u16, json: Value) -> Result<Value> {
+        if (200..300).contains(&status) {
+            return Ok(json);
+        }
+        let message = json["errorMessage"].as_str()
+            .or_else(|| json["error"].as_str())
+            .map(String::from)
+            .unwrap_or_else(|| format!("Server returned {}", status));
+        Err(CoreError::Auth(message))
+    }
+
+    fn session(json: &Value) -> Result<YggdrasilSession> {
+        let available_profiles = match json["availableProfiles"].as_array() {
+            Some(profiles) => profiles.iter().map(GameProfile::from_json).collect::<Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+        let selected_profile = match json.get("selectedProfile") {
+            Some(profile) if !profile.is_null() => Some(GameProfile::from_json(profile)?),
+            _ => None,
+        };
+        Ok(YggdrasilSession {
+            access_token: String::from(json["accessToken"].as_str().ok_or_else(|| missing("accessToken"))?),
+            client_token: String::from(json["clientToken"].as_str().ok_or_else(|| missing("clientToken"))?),
+            available_profiles,
+            selected_profile,
+        })
+    }
+
+    fn account(&self, session: YggdrasilSession, profile: GameProfile) -> YggdrasilAccount {
+        YggdrasilAccount {
+            server: self.server.clone(),
+            name: profile.name,
+            uuid: profile.id,
+            access_token: session.access_token,
+            client_token: session.client_token,
+        }
+    }
+
+    // 用户名密码登录
+    pub fn authenticate(&self, username: &str, password: &str, client_token: &str) -> Result<YggdrasilSession> {
+        let (status, json) = self.post("authenticate", json!({
+            "agent": {"name": "Minecraft", "version": 1},
+            "username": username,
+            "password": password,
+            "clientToken": client_token,
+            "requestUser": false
+        }))?;
+        YggdrasilAuth::session(&YggdrasilAuth::check(status, json)?)
+    }
+
+    // 选择角色，服务器已经选好时直接使用
+    pub fn select_profile(&self, session: YggdrasilSession, profile: Option<&GameProfile>) -> Result<YggdrasilAccount> {
+        if let Some(selected) = session.selected_profile.clone() {
+            return Ok(self.account(session, selected));
+        }
+        let profile = match profile {
+            Some(profile) => profile.clone(),
+            // 只有一个角色时不需要用户选择
+            None if session.available_profiles.len() == 1 => session.available_profiles[0].clone(),
+            None => return Err(CoreError::Auth(String::from("A profile must be selected"))),
+        };
+        let (status, json) = self.post("refresh", json!({
+            "accessToken": session.access_token,
+            "clientToken": session.client_token,
+            "selectedProfile": profile
+        }))?;
+        let refreshed = YggdrasilAuth::session(&YggdrasilAuth::check(status, json)?)?;
+        let profile = refreshed.selected_profile.clone().unwrap_or(profile);
+        Ok(self.account(refreshed, profile))
+    }
+
+    pub fn refresh(&self, account: &YggdrasilAccount) -> Result<YggdrasilAccount> {
+        let (status, json) = self.post("refresh", json!({
+            "accessToken": account.access_token,
+            "clientToken": account.client_token
+        }))?;
+        let session = YggdrasilAuth::session(&YggdrasilAuth::check(status, json)?)?;
+        let profile = session.selected_profile.clone().unwrap_or(GameProfile {
+            id: account.uuid.clone(),
+            name: account.name.clone(),
+        });
+        Ok(self.account(session, profile))
+    }
+
+    // 令牌是否仍然有效
+    pub fn validate(&self, account: &YggdrasilAccount) -> Result<bool> {
+        let (status, json) = self.post("validate", json!({
+            "accessToken": account.access_token,
+            "clientToken": account.client_token
+        }))?;
+        match status {
+            204 | 200 => Ok(true),
+            403 => Ok(false),
+            _ => YggdrasilAuth::check(status, json).map(|_| false),
+        }
+    }
+
+    pub fn invalidate(&self, account: &YggdrasilAccount) -> Result<()> {
+        let (status, json) = self.post("invalidate", json!({
+            "accessToken": account.access_token,
+            "clientToken": account.client_token
+        }))?;
+        YggdrasilAuth::check(status, json).map(|_| ())
+    }
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// 下载时校验过的SHA-256保存在jar旁边，启动时不访问网络也能校验
+fn checksum_path(jar: &Path) -> PathBuf {
+    jar.with_extension("jar.sha256")
+}
+
+// 下载最新的authlib-injector到dir，已经存在且校验通过时直接返回
+pub fn download_authlib_injector(client: &Client, sources: &DownloadSources, latest_url: &str, dir: &Path) -> Result<PathBuf> {
+    let latest: Value = serde_json::from_str(&sources.get_text(client, latest_url)?)?;
+    let version = latest["version"].as_str().ok_or_else(|| missing("version"))?;
+    let url = latest["download_url"].as_str().ok_or_else(|| missing("download_url"))?;
+    let sha256 = latest["checksums"]["sha256"].as_str().map(str::to_lowercase);
+    let path = dir.join(format!("authlib-injector-{}.jar", version));
+
+    let verified = |path: &Path| -> Result<bool> {
+        Ok(path.is_file() && match &sha256 {
+            Some(sha256) => &file_sha256(path)? == sha256,
+            None => true,
+        })
+    };
+    if !verified(&path)? {
+        create_dir_all(dir)?;
+        sources.download(client, &DownloadTask::new(String::from(url), path.clone()), 3, &CancelToken::new())?;
+        if !verified(&path)? {
+            let _ = std::fs::remove_file(&path);
+            return Err(CoreError::Checksum(String::from(url)));
+        }
+    }
+    if let Some(sha256) = &sha256 {
+        write(checksum_path(&path), sha256)?;
+    }
+    Ok(path)
+}
+
+// 启动时加入的JVM参数
+pub fn javaagent_argument(jar: &Path, server: &str) -> String {
+    format!("-javaagent:{}={}", jar.to_string_lossy(), server)
+}
+
+// dir里已经下载的最新版本的authlib-injector，不访问网络，只使用与保存的SHA-256一致的文件
+pub fn cached_authlib_injector(dir: &Path) -> Option<PathBuf> {
+    let version = |path: &Path| -> Option<Vec<u32>> {
+        let name = path.file_name()?.to_str()?;
+        let version = name.strip_prefix("authlib-injector-")?.strip_suffix(".jar")?;
+        version.split('.').map(|n| n.parse().ok()).collect()
+    };
+    let verified = |path: &Path| -> bool {
+        match (read_to_string(checksum_path(path)), file_sha256(path)) {
+            (Ok(expected), Ok(actual)) => expected.trim().eq_ignore_ascii_case(&actual),
+            _ => false,
+        }
+    };
+    let mut jars: Vec<(Vec<u32>, PathBuf)> = read_dir(dir).ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter_map(|path| Some((version(&path)?, path)))
+        .collect();
+    jars.sort();
+    jars.into_iter().rev().map(|(_, path)| path).find(|path| verified(path))
+}
+
+// 使用官方地址下载或更新authlib-injector，无法访问网络时使用已经下载的版本
+pub fn authlib_injector(dir: &Path) -> Result<PathBuf> {
+    download_authlib_injector(&Client::new(), &DownloadSources::official(), AUTHLIB_INJECTOR_LATEST, dir)
+        .or_else(|e| cached_authlib_injector(dir).ok_or(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Response, TestServer};
+
+    // 本地的Yggdrasil服务器，密码为"password"，有两个角色
+    fn yggdrasil_server() -> TestServer {
+        TestServer::start(|req| {
+            let body: Value = serde_json::from_slice(&req.body).unwrap_or(Value::Null);
+            let profiles = json!([
+                {"id": "11111111111111111111111111111111", "name": "Alice"},
+                {"id": "22222222222222222222222222222222", "name": "Bob"}
+            ]);
+            match req.path.as_str() {
+                "/api/yggdrasil/authserver/authenticate" => {
+                    if body["password"] != "password" {
+                        return Response::json(403, &json!({
+                            "error": "ForbiddenOperationException",
+                            "errorMessage": "Invalid credentials. Invalid username or password."
+                        }));
+                    }
+                    Response::json(200, &json!({
+                        "accessToken": "token-1",
+                        "clientToken": body["clientToken"],
+                        "availableProfiles": profiles
+                    }))
+                }
+                "/api/yggdrasil/authserver/refresh" => {
+                    let selected = if body["selectedProfile"].is_null() {
+                        json!({"id": "22222222222222222222222222222222", "name": "Bob"})
+                    } else {
+                        body["selectedProfile"].clone()
+                    };
+                    Response::json(200, &json!({
+                        "accessToken": format!("{}+", body["accessToken"].as_str().unwrap()),
+                        "clientToken": body["clientToken"],
+                        "selectedProfile": selected
+                    }))
+                }
+                "/api/yggdrasil/authserver/validate" if body["accessToken"] == "token-1+" => Response::new(204, ""),
+                "/api/yggdrasil/authserver/validate" => Response::json(403, &json!({"error": "ForbiddenOperationException"})),
+                "/api/yggdrasil/authserver/invalidate" => Response::new(204, ""),
+                _ => Response::not_found(),
+            }
+        })
+    }
+
+    #[test]
+    fn yggdrasil_flow() {
+        let server = yggdrasil_server();
+        let auth = YggdrasilAuth::new(&server.url("/api/yggdrasil/"));
+        assert!(matches!(auth.authenticate("alice@example.com", "wrong", "client"), Err(CoreError::Auth(_))));
+
+        let session = auth.authenticate("alice@example.com", "password", "client").unwrap();
+        assert_eq!(session.available_profiles.len(), 2);
+        assert!(auth.select_profile(session.clone(), None).is_err());
+
+        let account = auth.select_profile(session.clone(), Some(&session.available_profiles[1])).unwrap();
+        assert_eq!(account.name, "Bob");
+        assert_eq!(account.access_token, "token-1+");
+        assert_eq!(account.client_token, "client");
+        assert_eq!(account.server, server.url("/api/yggdrasil"));
+        assert!(auth.validate(&account).unwrap());
+
+        let refreshed = auth.refresh(&account).unwrap();
+        assert_eq!(refreshed.access_token, "token-1++");
+        assert!(!auth.validate(&refreshed).unwrap());
+        auth.invalidate(&refreshed).unwrap();
+    }
+
+    #[test]
+    fn downloads_authlib_injector() {
+        let jar = b"authlib-injector".to_vec();
+        let sha256 = Sha256::digest(&jar).iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        let server = TestServer::start(move |req| match req.path.as_str() {
+            "/latest.json" => Response::json(200, &json!({
+                "version": "1.2.5",
+                "download_url": format!("http://{}/authlib-injector-1.2.5.jar", req.header("host").unwrap()),
+                "checksums": {"sha256": sha256}
+            })),
+            "/authlib-injector-1.2.5.jar" => Response::ok(jar.clone()),
+            _ => Response::not_found(),
+        });
+        let dir = std::env::temp_dir().join(format!("akiraka-authlib-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = download_authlib_injector(&Client::new(), &DownloadSources::official(), &server.url("/latest.json"), &dir).unwrap();
+        assert_eq!(path, dir.join("authlib-injector-1.2.5.jar"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"authlib-injector");
+
+        // 第二次不再下载
+        download_authlib_injector(&Client::new(), &DownloadSources::official(), &server.url("/latest.json"), &dir).unwrap();
+        assert_eq!(server.requests().iter().filter(|r| r.ends_with(".jar")).count(), 1);
+        // 离线时使用校验通过的最新的已下载版本
+        assert_eq!(cached_authlib_injector(&dir), Some(path.clone()));
+        std::fs::write(dir.join("authlib-injector-1.2.10.jar"), b"newer").unwrap();
+        std::fs::write(dir.join("authlib-injector-latest.jar"), b"other").unwrap();
+        assert_eq!(cached_authlib_injector(&dir), Some(path.clone()));
+        let newer = Sha256::digest(b"newer").iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        std::fs::write(dir.join("authlib-injector-1.2.10.jar.sha256"), newer).unwrap();
+        assert_eq!(cached_authlib_injector(&dir), Some(dir.join("authlib-injector-1.2.10.jar")));
+        // 文件被改动后不再使用
+        std::fs::write(dir.join("authlib-injector-1.2.10.jar"), b"tampered").unwrap();
+        assert_eq!(cached_authlib_injector(&dir), Some(path.clone()));
+        std::fs::write(&path, b"tampered").unwrap();
+        assert_eq!(cached_authlib_injector(&dir), None);
+        assert_eq!(cached_authlib_injector(&dir.join("missing")), None);
+        assert_eq!(javaagent_argument(&path, "https://example.com/api/yggdrasil"),
+                   format!("-javaagent:{}=https://example.com/api/yggdrasil", path.to_string_lossy()));
+    }
+}
@@ -6,16 +6,22 @@ mod util;
 #[allow(dead_code)]
 mod widget;
 
-use crate::ui::hello_page;
+use crate::ui::{download_page, hello_page, instances_page, settings_page};
 use crate::widget::paged_widget;
 use crate::widget::window::WindowWidget;
+use akiraka_core::config::Config;
 use druid::im::Vector;
+use druid::widget::Controller;
 use druid::{
     AppDelegate, AppLauncher, BoxConstraints, Command, Data, DelegateCtx, Env, Event, EventCtx,
     Handled, LayoutCtx, Lens, LifeCycle, LifeCycleCtx, LocalizedString, PaintCtx, Screen, Size,
-    Target, UpdateCtx, Widget, WidgetPod, WindowDesc, WindowId, WindowState,
+    Target, TimerToken, UpdateCtx, Widget, WidgetExt, WidgetPod, WindowDesc, WindowId,
+    WindowState,
 };
 use rust_embed::RustEmbed;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 const WINDOW_TITLE: LocalizedString<AppState> = LocalizedString::new("Akiraka - Internal build");
 
@@ -25,12 +31,14 @@ pub struct Asset;
 
 pub struct Delegate {
     page: WidgetPod<AppState, Box<dyn Widget<AppState>>>,
+    config_path: PathBuf,
 }
 
 impl Delegate {
-    pub fn new(root: impl Widget<AppState> + 'static) -> Delegate {
+    pub fn new(root: impl Widget<AppState> + 'static, config_path: PathBuf) -> Delegate {
         Delegate {
             page: WidgetPod::new(Box::new(root)),
+            config_path,
         }
     }
 }
@@ -38,17 +46,27 @@ impl Delegate {
 impl AppDelegate<AppState> for Delegate {
     fn event(
         &mut self,
-        _ctx: &mut DelegateCtx,
+        ctx: &mut DelegateCtx,
         _window_id: WindowId,
         event: Event,
         data: &mut AppState,
         _env: &Env,
     ) -> Option<Event> {
-        match event {
-            Event::WindowConnected => {
-                data.java.push_back("java.exe".parse().unwrap());
-            }
-            _ => {}
+        if let Event::WindowConnected = event {
+            // 在后台搜索爪哇，找到后加入列表
+            let sink = ctx.get_external_handle();
+            let runtime_dir = data.config.runtime_dir();
+            std::thread::spawn(move || {
+                let found = akiraka_core::java::discover(Some(runtime_dir.as_path()));
+                sink.add_idle_callback(move |data: &mut AppState| {
+                    for java in found {
+                        let path = java.path.to_string_lossy().into_owned();
+                        if !data.java.contains(&path) {
+                            data.java.push_back(path);
+                        }
+                    }
+                });
+            });
         }
         Some(event)
     }
@@ -74,6 +92,13 @@ impl AppDelegate<AppState> for Delegate {
             Handled::No
         }
     }
+
+    // 关闭窗口时保存还没保存的修改
+    fn window_removed(&mut self, _id: WindowId, data: &mut AppState, _env: &Env, _ctx: &mut DelegateCtx) {
+        if let Err(e) = data.to_config().save(&self.config_path) {
+            data.report(format!("Could not save config: {}", e));
+        }
+    }
 }
 
 struct Empty {}
@@ -105,13 +130,139 @@ pub struct AppState {
     pub java: Vector<String>,
     pub file_open_type: String,
     pub minecraft_versions: Vector<(String, String)>,
+    pub account_name: String,
+    pub account_type: String,
+    // 实例列表：(名字, 版本)
+    pub instances: Vector<(String, String)>,
+    // 启动器设置，爪哇列表和页面另外保存在上面的字段里
+    pub config: Arc<Config>,
+    // 输入框里的JVM参数原文，保存时才按空格拆开
+    pub jvm_args: String,
+    // 最近的错误，显示在底栏上
+    pub message: String,
+}
+
+impl AppState {
+    // 当前状态对应的配置
+    fn to_config(&self) -> Config {
+        let mut config = Config::clone(&self.config);
+        config.java = self.java.iter().map(PathBuf::from).collect();
+        config.page = self.page_id.clone();
+        config.jvm_args = self.jvm_args.split_whitespace().map(String::from).collect();
+        config
+    }
+
+    // 读取游戏目录里的默认账户和实例列表，游戏目录改变后也要重新读取
+    fn load_game_root(&mut self) {
+        let no_account = (String::from("No Account"), String::from("Click to add one"));
+        (self.account_name, self.account_type) = match akiraka_core::account::AccountStore::open(self.config.accounts_file()) {
+            Ok(store) => match store.selected() {
+                Some(account) => (String::from(account.name()), String::from(account.type_name())),
+                None => no_account,
+            },
+            Err(e) => {
+                self.report(format!("Could not load accounts: {}", e));
+                no_account
+            }
+        };
+
+        self.instances = match akiraka_core::instance::InstanceStore::new(&self.config.game_root).list() {
+            Ok(list) => list.into_iter().map(|i| (i.manifest.name, i.manifest.version)).collect(),
+            Err(e) => {
+                self.report(format!("Could not load instances: {}", e));
+                Vector::new()
+            }
+        };
+    }
+
+    // 显示错误并写入日志
+    pub fn report(&mut self, message: String) {
+        util::log_error(&message);
+        self.message = message;
+    }
+}
+
+// 状态改变后等一会儿再保存配置，避免输入时每个字符都写一次文件
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+struct ConfigSaver {
+    path: PathBuf,
+    timer: TimerToken,
+    // 已经读取过账户和实例的游戏目录
+    game_root: PathBuf,
+}
+
+impl ConfigSaver {
+    fn new(path: PathBuf, game_root: PathBuf) -> ConfigSaver {
+        ConfigSaver {
+            path,
+            timer: TimerToken::INVALID,
+            game_root,
+        }
+    }
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for ConfigSaver {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppState, env: &Env) {
+        if let Event::Timer(token) = event {
+            if *token == self.timer {
+                self.timer = TimerToken::INVALID;
+                if let Err(e) = data.to_config().save(&self.path) {
+                    data.report(format!("Could not save config: {}", e));
+                }
+                // 输入完新的游戏目录后再重新读取，不在每次按键时读取
+                if data.config.game_root != self.game_root {
+                    self.game_root = data.config.game_root.clone();
+                    data.load_game_root();
+                }
+                return;
+            }
+        }
+        child.event(ctx, event, data, env)
+    }
+
+    fn update(&mut self, child: &mut W, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, env: &Env) {
+        let changed = !old_data.config.same(&data.config)
+            || !old_data.java.same(&data.java)
+            || old_data.jvm_args != data.jvm_args
+            || old_data.page_id != data.page_id;
+        if changed {
+            // 重新计时，停止修改后才保存
+            self.timer = ctx.request_timer(SAVE_DELAY);
+        }
+        child.update(ctx, old_data, data, env)
+    }
 }
 
 pub static mut PAGE_ID: &str = hello_page::ID;
 
+// 上次打开的页面
+fn page_id(page: &str) -> &'static str {
+    [hello_page::ID, instances_page::ID, download_page::ID, settings_page::ID]
+        .into_iter()
+        .find(|id| *id == page)
+        .unwrap_or(hello_page::ID)
+}
+
 fn main() {
+    // 启动时的错误，启动后显示在底栏上
+    let mut errors = Vec::new();
+
+    // 读取配置，读取失败时使用默认配置
+    let config_path = Config::default_path();
+    let config = Config::load(&config_path).unwrap_or_else(|e| {
+        errors.push(format!("Could not load config: {}", e));
+        Config::default()
+    });
+    let page = page_id(&config.page);
+    unsafe {
+        PAGE_ID = page;
+    }
+
     let scr_rect = Screen::get_monitors().get(0).unwrap().virtual_work_rect();
-    let main_window = WindowDesc::new(WindowWidget::new(paged_widget::PagedWidget::new()))
+    let root = WindowWidget::new(paged_widget::PagedWidget::new())
+        .controller(ConfigSaver::new(config_path.clone(), config.game_root.clone()));
+    let main_window = WindowDesc::new(root)
         .title(WINDOW_TITLE)
         .with_min_size((600.0, 400.0))
         .window_size((600.0, 400.0))
@@ -125,13 +276,25 @@ fn main() {
     let mut x = Vector::<(String, String)>::new();
     x.push_back(("114514".parse().unwrap(), "1919810".parse().unwrap()));
 
+    let java = config.java.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+    let jvm_args = config.jvm_args.join(" ");
     let mut initial_state = AppState {
-        page_id: String::new(),
+        page_id: String::from(page),
         global_search_bar_input: String::new(),
-        java: Vector::<String>::new(),
+        java,
         file_open_type: String::new(),
         minecraft_versions: x,
+        account_name: String::new(),
+        account_type: String::new(),
+        instances: Vector::new(),
+        config: Arc::new(config),
+        jvm_args,
+        message: String::new(),
     };
+    for error in errors {
+        initial_state.report(error);
+    }
+    initial_state.load_game_root();
     initial_state.java.append(Vector::new());
 
     let root = build_root_widget();
@@ -140,7 +303,7 @@ fn main() {
             // TODO: Environment
             theme::theme::init(_env);
         })
-        .delegate(Delegate::new(root))
+        .delegate(Delegate::new(root, config_path))
         .launch(initial_state)
         .expect("Failed to launch application");
 }
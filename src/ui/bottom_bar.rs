@@ -4,14 +4,13 @@ use crate::util::color_as_hex_string;
 use crate::widget::launch_button::LaunchButton;
 use crate::widget::profile_button::ProfileButton;
 use crate::{animations, AppState, Asset};
-use druid::widget::{Flex, Svg, SvgData, Widget};
+use druid::widget::{Flex, Label, LineBreaking, Svg, SvgData, Widget};
 use druid::{
     Affine, BoxConstraints, Color, Env, Event, EventCtx, Insets, LayoutCtx, LifeCycle,
     LifeCycleCtx, MouseButton, PaintCtx, Point, RenderContext, Size, UpdateCtx, Vec2, WidgetExt,
     WidgetPod,
 };
 use std::collections::HashMap;
-use akiraka_core::VersionInfo;
 
 pub const BOTTOM_BAR_HEIGHT: f64 = 56.0;
 pub const BOTTOM_BAR_HEIGHT_NAV: f64 = 40.0;
@@ -318,6 +317,14 @@ impl Widget<AppState> for PagedWidget {
     }
 }
 
+// 最近的错误
+fn build_message() -> impl Widget<AppState> {
+    Label::dynamic(|data: &AppState, _| data.message.clone())
+        .with_text_size(12.0)
+        .with_text_color(Color::rgb8(0xE0, 0x40, 0x40))
+        .with_line_break_mode(LineBreaking::Clip)
+}
+
 pub fn build_main() -> impl Widget<AppState> {
     let profile_button = ProfileButton::new()
         .fix_width(160.0)
@@ -333,11 +340,12 @@ pub fn build_main() -> impl Widget<AppState> {
     // .fix_width(crate::widget::window::TITLE_BAR_HEIGHT)
     // .fix_height(crate::widget::window::TITLE_BAR_HEIGHT);
 
-    let list_button = list_button.on_click(|ctx, _data, _env| {
+    let list_button = list_button.on_click(|ctx, data, _env| {
         unsafe {
             SELECTED = 1;
             crate::PAGE_ID = instances_page::ID;
         }
+        data.page_id = String::from(instances_page::ID);
         ctx.request_anim_frame();
     });
 
@@ -357,6 +365,7 @@ pub fn build_main() -> impl Widget<AppState> {
             crate::PAGE_ID = download_page::ID;
             // data.minecraft_versions.push_back(VersionInfo::empty());
         }
+        data.page_id = String::from(download_page::ID);
         ctx.request_anim_frame();
     });
 
@@ -370,11 +379,12 @@ pub fn build_main() -> impl Widget<AppState> {
     // .fix_width(crate::widget::window::TITLE_BAR_HEIGHT)
     // .fix_height(crate::widget::window::TITLE_BAR_HEIGHT);
 
-    let settings_button = settings_button.on_click(|ctx, _data, _env| {
+    let settings_button = settings_button.on_click(|ctx, data, _env| {
         unsafe {
             SELECTED = 1;
             crate::PAGE_ID = settings_page::ID;
         }
+        data.page_id = String::from(settings_page::ID);
         ctx.request_anim_frame();
     });
 
@@ -397,6 +407,8 @@ pub fn build_main() -> impl Widget<AppState> {
         .with_spacer(8.0)
         .with_child(settings_button)
         .with_flex_spacer(1.0)
+        .with_child(build_message())
+        .with_spacer(8.0)
         .with_child(launch_button)
         .center()
         .padding(Insets::new(12.0, 6.0, 12.0, 6.0))
@@ -415,11 +427,12 @@ pub fn build_nav() -> impl Widget<AppState> {
         hello_page::ID.parse().unwrap(),
     );
 
-    let home_button = home_button.on_click(|ctx, _data, _env| {
+    let home_button = home_button.on_click(|ctx, data, _env| {
         unsafe {
             SELECTED = 0;
             crate::PAGE_ID = hello_page::ID;
         }
+        data.page_id = String::from(hello_page::ID);
         ctx.request_anim_frame();
     });
     // List
@@ -431,10 +444,11 @@ pub fn build_nav() -> impl Widget<AppState> {
         instances_page::ID.parse().unwrap(),
     );
 
-    let list_button = list_button.on_click(|ctx, _data, _env| {
+    let list_button = list_button.on_click(|ctx, data, _env| {
         unsafe {
             crate::PAGE_ID = instances_page::ID;
         }
+        data.page_id = String::from(instances_page::ID);
         ctx.request_anim_frame();
     });
 
@@ -451,6 +465,7 @@ pub fn build_nav() -> impl Widget<AppState> {
         unsafe {
             crate::PAGE_ID = download_page::ID;
         }
+        data.page_id = String::from(download_page::ID);
         ctx.request_anim_frame();
     });
 
@@ -463,10 +478,11 @@ pub fn build_nav() -> impl Widget<AppState> {
         settings_page::ID.parse().unwrap(),
     );
 
-    let settings_button = settings_button.on_click(|ctx, _data, _env| {
+    let settings_button = settings_button.on_click(|ctx, data, _env| {
         unsafe {
             crate::PAGE_ID = settings_page::ID;
         }
+        data.page_id = String::from(settings_page::ID);
         ctx.request_anim_frame();
     });
 
@@ -479,6 +495,7 @@ pub fn build_nav() -> impl Widget<AppState> {
         .with_spacer(8.0)
         .with_child(settings_button)
         .with_flex_spacer(1.0)
+        .with_child(build_message())
         .center()
         .padding(Insets::new(12.0, 4.0, 12.0, 4.0))
         .fix_height(BOTTOM_BAR_HEIGHT_NAV)
@@ -1,21 +1,46 @@
-use druid::widget::{Flex, Label};
-use druid::{Data, Insets, UnitPoint, Widget, WidgetExt};
+use crate::AppState;
+use druid::widget::{Flex, Label, List, Scroll};
+use druid::{Insets, UnitPoint, Widget, WidgetExt};
 
 pub const ID: &str = "INSTANCES_PAGE";
 
-pub fn build<T: Data>() -> impl Widget<T> {
+// 一个实例：名字和版本
+fn build_instance() -> impl Widget<(String, String)> {
+    Flex::column()
+        .with_child(Label::dynamic(|data: &(String, String), _| data.0.clone()).with_text_size(14.0).align_left())
+        .with_child(Label::dynamic(|data: &(String, String), _| data.1.clone()).with_text_size(12.0).align_left())
+        .center()
+        .align_left()
+        .padding(Insets::uniform_xy(12.0, 4.0))
+        .expand_width()
+        .fix_height(48.0)
+}
+
+pub fn build() -> impl Widget<AppState> {
     let title = Label::new("Instances")
         .with_text_size(24.0)
         .fix_width(32.0)
         .expand_width()
         .padding(Insets::uniform_xy(12.0, 4.0));
 
+    let list = List::new(build_instance)
+        .with_spacing(0.0)
+        .lens(AppState::instances);
+
+    // 没有实例时显示提示
+    let empty = Label::new("No instances yet")
+        .with_text_size(12.0)
+        .padding(Insets::uniform_xy(12.0, 4.0))
+        .align_left();
+    let empty = druid::widget::Either::new(|data: &AppState, _| data.instances.is_empty(), empty, Flex::column());
+
     let body = Flex::column()
         .with_child(title)
-        .fix_width(160.0)
+        .with_child(empty)
+        .with_child(list)
         .padding(Insets::uniform_xy(8.0, 0.0));
 
-    let body = druid::widget::Scroll::new(body);
+    let body = Scroll::new(body).vertical();
 
     body.align_vertical(UnitPoint::TOP).align_left()
 }
@@ -3,19 +3,30 @@ use crate::util::color_as_hex_string;
 use crate::widget::icon::Icon;
 use crate::widget::side_bar_selection::SideBarSelection;
 use crate::{animations, AppState, Asset};
-use druid::widget::{CrossAxisAlignment, Flex, FlexParams, Label, List, Scroll, Svg, SvgData};
+use druid::widget::{
+    CrossAxisAlignment, Flex, FlexParams, Label, List, RadioGroup, Scroll, Stepper, Svg, SvgData,
+    TextBox,
+};
 use druid::{
     commands, Affine, BoxConstraints, Color, Data, Env, Event, EventCtx, FileDialogOptions,
-    FileSpec, Insets, LayoutCtx, LifeCycle, LifeCycleCtx, LocalizedString, MouseButton, PaintCtx,
+    FileSpec, Insets, LayoutCtx, Lens, LensExt, LifeCycle, LifeCycleCtx, LocalizedString, MouseButton, PaintCtx,
     RenderContext, Size, Target, UnitPoint, UpdateCtx, Vec2, Widget, WidgetExt, WidgetPod,
 };
+use akiraka_core::config::{Config, SourceKind};
+use akiraka_core::jvm::{GcPreset, MemorySetting};
+use akiraka_core::progress::{CancelToken, NoProgress};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 pub const ID: &str = "SETTINGS_PAGE";
 
 const ANIMATION_TIME: f64 = 0.3;
 static mut SELECTED: u64 = 0;
 
+// 点击安装爪哇时安装的运行时
+const DEFAULT_RUNTIME: &str = "java-runtime-gamma";
+
 const ICON_INSETS: Insets = Insets::uniform_xy(8., 2.);
 static mut SCHEDULED_TASKS: Vec<Box<dyn Fn(&mut AppState, &Env)>> = Vec::new();
 
@@ -581,8 +592,126 @@ impl<T: Data> Widget<T> for InstallJava<T> {
     }
 }
 
+// 读写配置里的一项，修改时复制一份新的配置
+fn config_lens<U: Data>(
+    get: impl Fn(&Config) -> U,
+    put: impl Fn(&mut Config, U),
+) -> impl Lens<AppState, U> {
+    AppState::config.map(
+        move |config: &Arc<Config>| get(config),
+        move |config: &mut Arc<Config>, value| put(Arc::make_mut(config), value),
+    )
+}
+
+// 一项设置：标题和控件
+fn setting_row(title: &str, widget: impl Widget<AppState> + 'static) -> impl Widget<AppState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(title).with_text_size(12.0))
+        .with_spacer(4.0)
+        .with_child(widget)
+        .padding(Insets::uniform_xy(0.0, 6.0))
+}
+
 fn build_settings() -> impl Widget<AppState> {
+    let game_root = TextBox::new()
+        .expand_width()
+        .lens(config_lens(
+            |c| c.game_root.to_string_lossy().into_owned(),
+            |c, v: String| c.game_root = PathBuf::from(v),
+        ));
+
+    let download_source = RadioGroup::column(vec![
+        ("Official", SourceKind::Official as u8),
+        ("BMCLAPI", SourceKind::Bmclapi as u8),
+        ("Custom", SourceKind::Custom as u8),
+    ])
+    .lens(config_lens(
+        |c| c.download_source as u8,
+        |c, v: u8| {
+            c.download_source = match v {
+                1 => SourceKind::Bmclapi,
+                2 => SourceKind::Custom,
+                _ => SourceKind::Official,
+            }
+        },
+    ));
+
+    let custom_mirror = TextBox::new()
+        .with_placeholder("https://")
+        .expand_width()
+        .lens(config_lens(
+            |c| c.custom_mirror.clone().unwrap_or_default(),
+            |c, v: String| c.custom_mirror = Some(v).filter(|v| !v.is_empty()),
+        ));
+
+    let threads = Flex::row()
+        .with_child(Label::dynamic(|data: &f64, _| format!("{}", data)))
+        .with_spacer(8.0)
+        .with_child(Stepper::new().with_range(1.0, 64.0).with_step(1.0))
+        .lens(config_lens(|c| c.threads as f64, |c, v: f64| c.threads = v as usize));
+
+    // 内存：自动或固定大小
+    let memory_mode = RadioGroup::row(vec![("Auto", 0u8), ("Fixed", 1u8)]).lens(config_lens(
+        |c| matches!(c.memory, MemorySetting::Fixed { .. }) as u8,
+        |c, v: u8| match (v, c.memory) {
+            (0, _) => c.memory = MemorySetting::Auto,
+            (_, MemorySetting::Auto) => c.memory = MemorySetting::Fixed { max: 4096, min: None },
+            _ => {}
+        },
+    ));
+
+    let memory_max = Flex::row()
+        .with_child(Label::dynamic(|data: &f64, _| format!("{} MB", data)))
+        .with_spacer(8.0)
+        .with_child(Stepper::new().with_range(512.0, 65536.0).with_step(256.0))
+        .lens(config_lens(
+            |c| match c.memory {
+                MemorySetting::Fixed { max, .. } => max as f64,
+                MemorySetting::Auto => 4096.0,
+            },
+            |c, v: f64| {
+                if let MemorySetting::Fixed { min, .. } = c.memory {
+                    c.memory = MemorySetting::Fixed { max: v as u32, min };
+                }
+            },
+        ))
+        .disabled_if(|data: &AppState, _| data.config.memory == MemorySetting::Auto);
+
+    let gc = RadioGroup::row(vec![
+        ("Default", GcPreset::Default as u8),
+        ("G1", GcPreset::G1 as u8),
+        ("ZGC", GcPreset::Zgc as u8),
+        ("Aikar", GcPreset::Aikar as u8),
+    ])
+    .lens(config_lens(
+        |c| c.gc as u8,
+        |c, v: u8| {
+            c.gc = match v {
+                1 => GcPreset::G1,
+                2 => GcPreset::Zgc,
+                3 => GcPreset::Aikar,
+                _ => GcPreset::Default,
+            }
+        },
+    ));
+
+    // 以空格分隔的自定义参数，输入时保留原文，不然打不出空格
+    let jvm_args = TextBox::new()
+        .with_placeholder("-Dfile.encoding=UTF-8")
+        .expand_width()
+        .lens(AppState::jvm_args);
+
     let game = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(setting_row("Game Directory", game_root))
+        .with_child(setting_row("Memory", memory_mode))
+        .with_child(setting_row("Maximum Memory", memory_max))
+        .with_child(setting_row("Garbage Collector", gc))
+        .with_child(setting_row("JVM Arguments", jvm_args))
+        .with_child(setting_row("Download Source", download_source))
+        .with_child(setting_row("Custom Mirror", custom_mirror))
+        .with_child(setting_row("Download Threads", threads))
         .padding(Insets::uniform_xy(12.0, 12.0))
         .background(theme::COLOR_BACKGROUND_LIGHT)
         .border(theme::COLOR_BORDER_DARK, 1.0)
@@ -592,15 +721,22 @@ fn build_settings() -> impl Widget<AppState> {
 
     let body = Flex::column()
         .with_child(
-            Label::new(LocalizedString::new("None"))
+            Label::new(LocalizedString::new("Launcher"))
                 .with_text_size(14.0)
                 .align_left(),
         )
         .with_spacer(8.0)
         .with_child(game)
-        .padding(Insets::new(4.0, 4.0, 32.0, 4.0));
+        .padding(Insets::new(4.0, 4.0, 32.0, 4.0))
+        .align_vertical(UnitPoint::TOP)
+        .align_left();
 
-    body.align_vertical(UnitPoint::TOP).align_left()
+    let scroll = Scroll::new(body)
+        .vertical()
+        .expand()
+        .padding(Insets::new(0.0, 0.0, 0.0, 84.0));
+
+    scroll.align_vertical(UnitPoint::TOP).align_left()
 }
 
 fn build_game() -> impl Widget<AppState> {
@@ -637,7 +773,38 @@ fn build_game() -> impl Widget<AppState> {
     let install_java = InstallJava::<AppState>::new()
         .expand_width()
         .fix_height(56.0)
-        .align_left();
+        .align_left()
+        .on_click(|ctx, data: &mut AppState, _env| {
+            // 在后台安装官方运行时，装好后加入列表
+            let sink = ctx.get_external_handle();
+            let runtime_dir = data.config.runtime_dir();
+            // 使用设置里的下载源和线程数
+            let sources = data.config.download_sources();
+            let threads = data.config.threads;
+            std::thread::spawn(move || {
+                let runtimes = akiraka_core::runtime::JavaRuntimes::new(runtime_dir)
+                    .with_sources(sources)
+                    .with_pool_size(threads);
+                let result = runtimes.install(
+                    DEFAULT_RUNTIME,
+                    Arc::new(NoProgress),
+                    &CancelToken::new(),
+                );
+                match result {
+                    Ok(java) => {
+                        let path = java.path.to_string_lossy().into_owned();
+                        sink.add_idle_callback(move |data: &mut AppState| {
+                            if !data.java.contains(&path) {
+                                data.java.push_back(path);
+                            }
+                        });
+                    }
+                    Err(e) => sink.add_idle_callback(move |data: &mut AppState| {
+                        data.report(format!("Could not install Java: {}", e));
+                    }),
+                }
+            });
+        });
 
     let list_layout = Flex::column()
         .with_child(list)
@@ -2,6 +2,8 @@ use crate::Asset;
 use druid::piet::ImageFormat;
 use druid::{Color, ImageBuf, Size};
 use image::imageops::FilterType;
+use std::fs::{create_dir_all, OpenOptions};
+use std::io::Write;
 #[allow(dead_code)]
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -37,3 +39,14 @@ pub fn get_time() -> u128 {
         .unwrap()
         .as_millis()
 }
+
+// 把错误写到配置目录下的日志文件，没有控制台时也能找到
+pub fn log_error(message: &str) {
+    let Some(dir) = akiraka_core::config::config_dir() else {
+        return;
+    };
+    let _ = create_dir_all(&dir);
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(dir.join("launcher.log")) {
+        let _ = writeln!(file, "[{}] {}", get_time() / 1000, message);
+    }
+}
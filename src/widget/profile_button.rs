@@ -1,7 +1,8 @@
 use crate::util;
+use crate::AppState;
 use druid::widget::{Flex, Image, Label};
 use druid::{
-    theme, Affine, BoxConstraints, Data, Env, Event, EventCtx, Insets, LayoutCtx, LifeCycle,
+    theme, Affine, BoxConstraints, Env, Event, EventCtx, Insets, LayoutCtx, LifeCycle,
     LifeCycleCtx, MouseButton, PaintCtx, Point, RenderContext, Size, TextAlignment, UpdateCtx,
     Vec2, Widget, WidgetExt, WidgetPod,
 };
@@ -9,19 +10,20 @@ use image::imageops::FilterType;
 
 const LABEL_INSETS: Insets = Insets::uniform_xy(8., 2.);
 
-pub struct ProfileButton<T> {
+// 显示当前选中的账户
+pub struct ProfileButton {
     icon: Image,
-    layout: WidgetPod<T, Box<dyn Widget<T>>>,
+    layout: WidgetPod<AppState, Box<dyn Widget<AppState>>>,
 }
 
-impl<T: Data> ProfileButton<T> {
-    pub fn new() -> ProfileButton<T> {
-        let user_name = Label::new("Unknown User")
+impl ProfileButton {
+    pub fn new() -> ProfileButton {
+        let user_name = Label::new(|data: &AppState, _env: &Env| data.account_name.clone())
             .with_text_size(15.0)
             .with_text_alignment(TextAlignment::Start)
             .expand_width()
             .fix_height(18.0);
-        let user_type = Label::new("Unknown Type")
+        let user_type = Label::new(|data: &AppState, _env: &Env| data.account_type.clone())
             .with_text_size(12.0)
             .with_text_alignment(TextAlignment::Start)
             .expand_width()
@@ -43,8 +45,8 @@ impl<T: Data> ProfileButton<T> {
     }
 }
 
-impl<T: Data> Widget<T> for ProfileButton<T> {
-    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut T, _env: &Env) {
+impl Widget<AppState> for ProfileButton {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut AppState, _env: &Env) {
         match event {
             Event::MouseDown(event) => {
                 if !ctx.is_disabled() && event.button == MouseButton::Left {
@@ -62,7 +64,7 @@ impl<T: Data> Widget<T> for ProfileButton<T> {
         }
     }
 
-    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &AppState, env: &Env) {
         if let LifeCycle::HotChanged(_) | LifeCycle::DisabledChanged(_) = event {
             ctx.request_paint();
         }
@@ -70,11 +72,11 @@ impl<T: Data> Widget<T> for ProfileButton<T> {
         self.layout.lifecycle(ctx, event, data, env);
     }
 
-    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &AppState, data: &AppState, env: &Env) {
         self.layout.update(ctx, data, env);
     }
 
-    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &AppState, env: &Env) -> Size {
         let padding = Size::new(LABEL_INSETS.x_value(), LABEL_INSETS.y_value());
 
         let icon_bc = bc.loosen();
@@ -90,7 +92,7 @@ impl<T: Data> Widget<T> for ProfileButton<T> {
         bc.min()
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppState, env: &Env) {
         let is_active = ctx.is_active() && !ctx.is_disabled();
         let is_hot = ctx.is_hot();
         let size = ctx.size();